@@ -0,0 +1,21 @@
+/*
+ *  build.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use conductor_schema::{TYPESCRIPT_PATH, generate_typescript, schema};
+
+fn main() {
+    println!("cargo::rerun-if-changed={TYPESCRIPT_PATH}");
+    println!("cargo::rerun-if-changed=schema/commands.json");
+
+    // The payload is built from the TypeScript types, make sure they match the schema
+    let current = std::fs::read_to_string(TYPESCRIPT_PATH).unwrap_or_default();
+    if current != generate_typescript(schema()) {
+        panic!("{TYPESCRIPT_PATH} is out of date, run `cargo run -p conductor-schema`");
+    }
+}
@@ -0,0 +1,19 @@
+/*
+ *  build.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use std::path::PathBuf;
+
+fn main() {
+    let out = PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("commands.rs");
+    std::fs::write(
+        out,
+        conductor_schema::generate_rust(conductor_schema::schema()),
+    )
+    .expect("failed to write generated commands");
+}
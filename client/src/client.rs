@@ -0,0 +1,347 @@
+/*
+ *  client.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::commands::Command;
+use crate::signing::SigningKey;
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::sync::{broadcast, oneshot, watch};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Shortest wait before connecting again, doubled after every failed try
+const MIN_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest wait before connecting again
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to connect: {0}")]
+    Connect(tokio_tungstenite::tungstenite::Error),
+    #[error("Lost connection to the server")]
+    Disconnected,
+    #[error("Client is closed")]
+    Closed,
+    #[error("No response after {} seconds", .0.as_secs_f32())]
+    Timeout(Duration),
+    #[error("Invalid arguments: {0}")]
+    InvalidArgs(serde_json::Error),
+    #[error("{0}")]
+    Command(String),
+    #[error("Server sent an invalid response: {0}")]
+    InvalidResponse(String),
+}
+
+/// Settings for [`Client::connect`]
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    /// Secret sent with every request
+    pub secret: Option<String>,
+    /// Sign every request with this key instead of sending a secret
+    pub signing_key: Option<SigningKey>,
+    /// How long to wait for each response
+    pub timeout: Duration,
+    /// Connect again when the connection drops. Calls made while disconnected fail right away.
+    pub reconnect: bool,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            secret: None,
+            signing_key: None,
+            timeout: Duration::from_secs(30),
+            reconnect: true,
+        }
+    }
+}
+
+type Responder = oneshot::Sender<Result<serde_json::Value, Error>>;
+
+struct Request {
+    message_id: u32,
+    text: String,
+}
+
+struct Shared {
+    options: ClientOptions,
+    next_id: AtomicU32,
+    /// Calls waiting for a response, by message ID
+    pending: Mutex<HashMap<u32, Responder>>,
+    requests: UnboundedSender<Request>,
+    events: broadcast::Sender<serde_json::Value>,
+    connected: watch::Sender<bool>,
+}
+
+impl Shared {
+    fn respond(&self, message_id: u32, res: Result<serde_json::Value, Error>) {
+        if let Some(responder) = self.pending.lock().unwrap().remove(&message_id) {
+            _ = responder.send(res);
+        }
+    }
+
+    fn fail_all(&self) {
+        for (_, responder) in self.pending.lock().unwrap().drain() {
+            _ = responder.send(Err(Error::Disconnected));
+        }
+    }
+
+    /// Routes a message from the server to the call waiting for it, or to the event subscribers.
+    fn dispatch(&self, text: &str) {
+        let Ok(msg) = serde_json::from_str::<serde_json::Value>(text) else {
+            return;
+        };
+
+        let message_id = match msg.get("messageId").and_then(|id| id.as_u64()) {
+            Some(id) => Some(id as u32),
+            // Errors sent before a request is accepted, like a wrong secret, have no ID. They
+            // answer the oldest request since the server handles them in order.
+            None if msg.get("success").is_some() => {
+                self.pending.lock().unwrap().keys().min().copied()
+            }
+            None => None,
+        };
+
+        match message_id {
+            Some(id) => self.respond(id, Ok(msg)),
+            None => _ = self.events.send(msg),
+        }
+    }
+}
+
+/// Async client for a Conductor server. Requests can be sent from many tasks at once, responses
+/// are matched to their calls by message ID. Cloning is cheap and shares the connection.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), conductor_client::Error> {
+/// use conductor_client::commands::{AppType, GetApps};
+/// use conductor_client::{Client, ClientOptions};
+///
+/// let client = Client::connect("ws://localhost:7355", ClientOptions::default()).await?;
+/// let res = client
+///     .call(&GetApps {
+///         type_filter: Some(vec![AppType::Game, AppType::Shortcut]),
+///         installed_only: Some(true),
+///     })
+///     .await?;
+/// println!("{:?}", res.app_ids);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Client {
+    shared: Arc<Shared>,
+}
+
+impl Client {
+    /// Connects to `url`, like `ws://localhost:7355`. Fails if the first connection can't be made,
+    /// later ones are retried in the background if `options.reconnect` is set.
+    pub async fn connect(url: &str, options: ClientOptions) -> Result<Self, Error> {
+        let (ws, _) = connect_async(url).await.map_err(Error::Connect)?;
+
+        let (requests, requests_rx) = unbounded_channel();
+        let shared = Arc::new(Shared {
+            options,
+            next_id: 1.into(),
+            pending: HashMap::new().into(),
+            requests,
+            events: broadcast::channel(64).0,
+            connected: watch::Sender::new(true),
+        });
+
+        tokio::spawn(run_connection(
+            url.to_owned(),
+            ws,
+            Arc::downgrade(&shared),
+            requests_rx,
+        ));
+
+        Ok(Self { shared })
+    }
+
+    /// Sends a command and returns its response. Fails with [`Error::Command`] if the server
+    /// reports an error.
+    pub async fn call<C: Command>(&self, command: &C) -> Result<C::Returns, Error> {
+        let args = serde_json::to_value(command).map_err(Error::InvalidArgs)?;
+        let res = self.call_raw(C::NAME, args).await?;
+
+        if res.get("success").and_then(|s| s.as_bool()) != Some(true) {
+            let error = res
+                .get("error")
+                .and_then(|e| e.as_str())
+                .unwrap_or("Command failed without an error message");
+            return Err(Error::Command(error.to_owned()));
+        }
+
+        C::Returns::deserialize(&res).map_err(|e| Error::InvalidResponse(e.to_string()))
+    }
+
+    /// Sends a command by name and returns the whole response, including failed ones. Useful for
+    /// commands this crate doesn't know about.
+    pub async fn call_raw(
+        &self,
+        command: &str,
+        args: serde_json::Value,
+    ) -> Result<serde_json::Value, Error> {
+        let shared = &self.shared;
+        let message_id = shared.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let signature = shared
+            .options
+            .signing_key
+            .as_ref()
+            .map(|key| key.sign(command, &args));
+        let mut req = json!({
+            "command": command,
+            "args": args,
+            "messageId": message_id,
+        });
+        if let Some(signature) = signature {
+            req["signature"] = json!(signature);
+        } else if let Some(secret) = &shared.options.secret {
+            req["secret"] = secret.as_str().into();
+        }
+
+        let (tx, rx) = oneshot::channel();
+        shared.pending.lock().unwrap().insert(message_id, tx);
+
+        let req = Request {
+            message_id,
+            text: req.to_string(),
+        };
+        if shared.requests.send(req).is_err() {
+            shared.pending.lock().unwrap().remove(&message_id);
+            return Err(Error::Closed);
+        }
+
+        match tokio::time::timeout(shared.options.timeout, rx).await {
+            Ok(Ok(res)) => res,
+            Ok(Err(_)) => Err(Error::Closed),
+            Err(_) => {
+                shared.pending.lock().unwrap().remove(&message_id);
+                Err(Error::Timeout(shared.options.timeout))
+            }
+        }
+    }
+
+    /// Subscribes to messages the server sends without a request.
+    pub fn events(&self) -> broadcast::Receiver<serde_json::Value> {
+        self.shared.events.subscribe()
+    }
+
+    /// Returns `true` while the client is connected.
+    pub fn is_connected(&self) -> bool {
+        *self.shared.connected.borrow()
+    }
+
+    /// Watches the connection state, useful for reloading data after a reconnect.
+    pub fn connection(&self) -> watch::Receiver<bool> {
+        self.shared.connected.subscribe()
+    }
+}
+
+/// Owns the socket, sends queued requests and hands out responses until every [`Client`] is dropped.
+async fn run_connection(
+    url: String,
+    mut ws: Socket,
+    shared: std::sync::Weak<Shared>,
+    mut requests: UnboundedReceiver<Request>,
+) {
+    loop {
+        loop {
+            tokio::select! {
+                msg = ws.next() => {
+                    let Some(shared) = shared.upgrade() else {
+                        return;
+                    };
+                    match msg {
+                        Some(Ok(Message::Text(text))) => shared.dispatch(&text),
+                        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                        Some(Ok(_)) => {}
+                    }
+                }
+                req = requests.recv() => {
+                    let Some(req) = req else {
+                        // All clients dropped
+                        _ = ws.close(None).await;
+                        return;
+                    };
+                    if ws.send(Message::text(req.text)).await.is_err() {
+                        if let Some(shared) = shared.upgrade() {
+                            shared.respond(req.message_id, Err(Error::Disconnected));
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+
+        let Some(state) = shared.upgrade() else {
+            return;
+        };
+        state.connected.send_replace(false);
+        state.fail_all();
+
+        if !state.options.reconnect {
+            return;
+        }
+        drop(state);
+
+        ws = match reconnect(&url, &shared, &mut requests).await {
+            Some(ws) => ws,
+            None => return,
+        };
+
+        if let Some(state) = shared.upgrade() {
+            state.connected.send_replace(true);
+        }
+    }
+}
+
+/// Connects again with exponential backoff. Requests sent in the meantime fail right away.
+/// Returns `None` once every [`Client`] is dropped.
+async fn reconnect(
+    url: &str,
+    shared: &std::sync::Weak<Shared>,
+    requests: &mut UnboundedReceiver<Request>,
+) -> Option<Socket> {
+    let mut delay = MIN_RECONNECT_DELAY;
+
+    loop {
+        let sleep = tokio::time::sleep(delay);
+        tokio::pin!(sleep);
+
+        loop {
+            tokio::select! {
+                _ = &mut sleep => break,
+                req = requests.recv() => {
+                    let req = req?;
+                    shared.upgrade()?.respond(req.message_id, Err(Error::Disconnected));
+                }
+            }
+        }
+
+        if let Ok((ws, _)) = connect_async(url).await {
+            return Some(ws);
+        }
+
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
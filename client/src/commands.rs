@@ -0,0 +1,28 @@
+/*
+ *  commands.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Argument and return types for every command, generated from `schema/commands.json`. Each
+//! command is a struct with its arguments, the response type is [`Command::Returns`].
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// A request that can be sent with [`Client::call`](crate::Client::call).
+pub trait Command: Serialize {
+    /// Command name sent in the `command` field
+    const NAME: &'static str;
+    /// Data returned when the command succeeds
+    type Returns: DeserializeOwned;
+}
+
+/// Response for commands that don't return anything
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Empty {}
+
+include!(concat!(env!("OUT_DIR"), "/commands.rs"));
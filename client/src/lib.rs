@@ -0,0 +1,18 @@
+/*
+ *  lib.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Async client for [Conductor](https://github.com/tancop/Conductor). Every command from
+//! `schema/commands.json` has a typed request struct in [`commands`], send it with [`Client::call`].
+
+mod client;
+pub mod commands;
+pub mod signing;
+
+pub use client::{Client, ClientOptions, Error};
+pub use signing::SigningKey;
@@ -0,0 +1,369 @@
+/*
+ *  client.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use conductor_client::commands::{AppType, GetAppInfo, GetApps, RunApp};
+use conductor_client::signing::{Signature, canonical_request, verify};
+use conductor_client::{Client, ClientOptions, Error, SigningKey};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+
+type ServerSocket = WebSocketStream<TcpStream>;
+
+/// Starts a server on a random port that hands every accepted connection to the test.
+async fn mock_server() -> (String, UnboundedReceiver<ServerSocket>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let (tx, rx) = unbounded_channel();
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            if tx.send(ws).is_err() {
+                return;
+            }
+        }
+    });
+
+    (url, rx)
+}
+
+async fn recv_json(ws: &mut ServerSocket) -> serde_json::Value {
+    loop {
+        let msg = ws.next().await.unwrap().unwrap();
+        if let Message::Text(text) = msg {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+async fn send_json(ws: &mut ServerSocket, msg: serde_json::Value) {
+    ws.send(Message::text(msg.to_string())).await.unwrap();
+}
+
+fn options() -> ClientOptions {
+    ClientOptions {
+        timeout: Duration::from_secs(5),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn typed_call() {
+    let (url, mut conns) = mock_server().await;
+    let client = Client::connect(
+        &url,
+        ClientOptions {
+            secret: Some("hunter2".to_owned()),
+            ..options()
+        },
+    )
+    .await
+    .unwrap();
+
+    let call = tokio::spawn(async move {
+        client
+            .call(&GetApps {
+                type_filter: Some(vec![AppType::Game, AppType::Shortcut]),
+                installed_only: Some(true),
+            })
+            .await
+    });
+
+    let mut ws = conns.recv().await.unwrap();
+    let req = recv_json(&mut ws).await;
+    assert_eq!(req["command"], "GetApps");
+    assert_eq!(req["secret"], "hunter2");
+    assert_eq!(
+        req["args"],
+        json!({ "typeFilter": [1, 1073741824], "installedOnly": true })
+    );
+
+    send_json(
+        &mut ws,
+        json!({ "success": true, "messageId": req["messageId"], "appIds": [730, 440] }),
+    )
+    .await;
+
+    let res = call.await.unwrap().unwrap();
+    assert_eq!(res.app_ids, vec![730, 440]);
+}
+
+#[tokio::test]
+async fn signed_call() {
+    let (url, mut conns) = mock_server().await;
+    let client = Client::connect(
+        &url,
+        ClientOptions {
+            secret: Some("hunter2".to_owned()),
+            signing_key: Some(SigningKey {
+                id: "laptop".to_owned(),
+                key: "correct horse".to_owned(),
+            }),
+            ..options()
+        },
+    )
+    .await
+    .unwrap();
+
+    let call = tokio::spawn(async move { client.call(&RunApp { app_id: 730 }).await });
+
+    let mut ws = conns.recv().await.unwrap();
+    let req = recv_json(&mut ws).await;
+    assert!(req.get("secret").is_none(), "the secret shouldn't be sent");
+
+    let signature: Signature = serde_json::from_value(req["signature"].clone()).unwrap();
+    assert_eq!(signature.key_id, "laptop");
+    let canonical = canonical_request(
+        &signature.key_id,
+        signature.timestamp,
+        &signature.nonce,
+        "RunApp",
+        &req["args"],
+    );
+    assert!(canonical.ends_with("\nRunApp\n{\"appId\":730}"));
+    assert!(verify("correct horse", &canonical, &signature.mac));
+    assert!(!verify("wrong horse", &canonical, &signature.mac));
+
+    send_json(
+        &mut ws,
+        json!({ "success": true, "messageId": req["messageId"] }),
+    )
+    .await;
+    call.await.unwrap().unwrap();
+}
+
+#[test]
+fn canonical_args_are_sorted() {
+    let canonical = |args| canonical_request("key", 1, "nonce", "SetArtwork", &args);
+    assert_eq!(
+        canonical(json!({ "kind": "hero", "appId": 10, "nested": { "b": [1, "é"], "a": null } })),
+        canonical(json!({ "nested": { "a": null, "b": [1, "é"] }, "appId": 10, "kind": "hero" }))
+    );
+    assert_eq!(
+        canonical(json!({ "b": 1, "a": [true] })),
+        "key\n1\nnonce\nSetArtwork\n{\"a\":[true],\"b\":1}"
+    );
+}
+
+#[tokio::test]
+async fn responses_out_of_order() {
+    let (url, mut conns) = mock_server().await;
+    let client = Client::connect(&url, options()).await.unwrap();
+
+    let calls: Vec<_> = [730, 440, 570]
+        .into_iter()
+        .map(|app_id| {
+            let client = client.clone();
+            tokio::spawn(async move { client.call(&GetAppInfo { app_id }).await })
+        })
+        .collect();
+
+    let mut ws = conns.recv().await.unwrap();
+    let mut requests = Vec::new();
+    for _ in 0..3 {
+        requests.push(recv_json(&mut ws).await);
+    }
+
+    for req in requests.iter().rev() {
+        let app_id = req["args"]["appId"].clone();
+        send_json(
+            &mut ws,
+            json!({
+                "success": true,
+                "messageId": req["messageId"],
+                "id": app_id,
+                "type": 1,
+                "installed": true,
+                "displayName": format!("App {app_id}"),
+                "storeTags": [],
+                "launchOptions": "",
+                "developerName": "Valve",
+                "installFolder": 0,
+            }),
+        )
+        .await;
+    }
+
+    for (call, app_id) in calls.into_iter().zip([730, 440, 570]) {
+        let info = call.await.unwrap().unwrap();
+        assert_eq!(info.id, app_id);
+        assert_eq!(info.display_name, format!("App {app_id}"));
+        assert_eq!(info.app_type, AppType::Game);
+    }
+}
+
+#[tokio::test]
+async fn command_error() {
+    let (url, mut conns) = mock_server().await;
+    let client = Client::connect(&url, options()).await.unwrap();
+
+    let call = tokio::spawn(async move { client.call(&RunApp { app_id: 730 }).await });
+
+    let mut ws = conns.recv().await.unwrap();
+    let req = recv_json(&mut ws).await;
+    send_json(
+        &mut ws,
+        json!({ "success": false, "messageId": req["messageId"], "error": "App with ID 730 not installed" }),
+    )
+    .await;
+
+    match call.await.unwrap() {
+        Err(Error::Command(error)) => assert_eq!(error, "App with ID 730 not installed"),
+        other => panic!("expected a command error, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn error_without_message_id() {
+    let (url, mut conns) = mock_server().await;
+    let client = Client::connect(&url, options()).await.unwrap();
+
+    let call = tokio::spawn(async move { client.call(&RunApp { app_id: 730 }).await });
+
+    let mut ws = conns.recv().await.unwrap();
+    recv_json(&mut ws).await;
+    send_json(
+        &mut ws,
+        json!({ "success": false, "error": "Wrong secret! Are you a hacker?" }),
+    )
+    .await;
+
+    assert!(matches!(call.await.unwrap(), Err(Error::Command(_))));
+}
+
+#[tokio::test]
+async fn raw_call_returns_failures() {
+    let (url, mut conns) = mock_server().await;
+    let client = Client::connect(&url, options()).await.unwrap();
+
+    let call = tokio::spawn(async move { client.call_raw("Unknown", json!({})).await });
+
+    let mut ws = conns.recv().await.unwrap();
+    let req = recv_json(&mut ws).await;
+    send_json(
+        &mut ws,
+        json!({ "success": false, "messageId": req["messageId"], "error": "Unknown command" }),
+    )
+    .await;
+
+    let res = call.await.unwrap().unwrap();
+    assert_eq!(res["success"], false);
+    assert_eq!(res["error"], "Unknown command");
+}
+
+#[tokio::test]
+async fn timeout() {
+    let (url, mut conns) = mock_server().await;
+    let client = Client::connect(
+        &url,
+        ClientOptions {
+            timeout: Duration::from_millis(100),
+            ..options()
+        },
+    )
+    .await
+    .unwrap();
+
+    let call = tokio::spawn(async move { client.call(&RunApp { app_id: 730 }).await });
+
+    // Keep the connection open without answering
+    let mut ws = conns.recv().await.unwrap();
+    recv_json(&mut ws).await;
+
+    assert!(matches!(call.await.unwrap(), Err(Error::Timeout(_))));
+}
+
+#[tokio::test]
+async fn events() {
+    let (url, mut conns) = mock_server().await;
+    let client = Client::connect(&url, options()).await.unwrap();
+    let mut events = client.events();
+
+    let mut ws = conns.recv().await.unwrap();
+    send_json(&mut ws, json!({ "event": "AppStarted", "appId": 730 })).await;
+
+    let event = events.recv().await.unwrap();
+    assert_eq!(event["event"], "AppStarted");
+}
+
+#[tokio::test]
+async fn pending_calls_fail_on_disconnect() {
+    let (url, mut conns) = mock_server().await;
+    let client = Client::connect(
+        &url,
+        ClientOptions {
+            reconnect: false,
+            ..options()
+        },
+    )
+    .await
+    .unwrap();
+
+    let call = {
+        let client = client.clone();
+        tokio::spawn(async move { client.call(&RunApp { app_id: 730 }).await })
+    };
+
+    let mut ws = conns.recv().await.unwrap();
+    recv_json(&mut ws).await;
+    drop(ws);
+
+    assert!(matches!(call.await.unwrap(), Err(Error::Disconnected)));
+
+    // Without reconnecting the client is done for good
+    let mut connection = client.connection();
+    connection.wait_for(|connected| !connected).await.unwrap();
+    assert!(matches!(
+        client.call(&RunApp { app_id: 730 }).await,
+        Err(Error::Closed)
+    ));
+}
+
+#[tokio::test]
+async fn reconnects() {
+    let (url, mut conns) = mock_server().await;
+    let client = Client::connect(&url, options()).await.unwrap();
+    let mut connection = client.connection();
+
+    drop(conns.recv().await.unwrap());
+    connection.wait_for(|connected| !connected).await.unwrap();
+    assert!(!client.is_connected());
+
+    let mut ws = conns.recv().await.unwrap();
+    connection.wait_for(|connected| *connected).await.unwrap();
+
+    let call = tokio::spawn(async move { client.call(&RunApp { app_id: 730 }).await });
+
+    let req = recv_json(&mut ws).await;
+    send_json(
+        &mut ws,
+        json!({ "success": true, "messageId": req["messageId"] }),
+    )
+    .await;
+
+    call.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn connect_error() {
+    // Bind and drop a listener to find a port nobody is using
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    drop(listener);
+
+    assert!(matches!(
+        Client::connect(&url, options()).await,
+        Err(Error::Connect(_))
+    ));
+}
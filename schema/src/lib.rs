@@ -0,0 +1,233 @@
+/*
+ *  lib.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Command definitions shared by the server, the Rust client and the TypeScript payload.
+//! `commands.json` is the source of truth, the Rust and TypeScript types are generated from it.
+
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+mod rust;
+mod spec;
+mod typescript;
+mod validate;
+
+pub use rust::generate_rust;
+pub use spec::{ASYNCAPI_PATH, OPENAPI_PATH, generate_asyncapi, generate_openapi};
+pub use typescript::generate_typescript;
+pub use validate::FieldError;
+
+/// Raw schema file
+pub const SCHEMA_JSON: &str = include_str!("../commands.json");
+
+/// Where the generated TypeScript types live, relative to the repo root
+pub const TYPESCRIPT_PATH: &str = "src/js/commands.ts";
+
+/// Who runs a command
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Handler {
+    /// The payload inside Steam
+    Steam,
+    /// The Conductor server itself
+    Server,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HandlerGroup {
+    pub name: Handler,
+    /// Name of the TypeScript type listing these commands
+    pub type_name: String,
+    pub doc: String,
+}
+
+/// Type of a field, written as `bool`, `string`, `u32`, `i32`, `u64`, a type name or any of
+/// those followed by `[]`
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub enum TypeRef {
+    Bool,
+    String,
+    U32,
+    I32,
+    U64,
+    Array(Box<TypeRef>),
+    Named(String),
+}
+
+impl TryFrom<String> for TypeRef {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if let Some(inner) = value.strip_suffix("[]") {
+            return Ok(TypeRef::Array(Box::new(inner.to_owned().try_into()?)));
+        }
+
+        Ok(match value.as_str() {
+            "bool" => TypeRef::Bool,
+            "string" => TypeRef::String,
+            "u32" => TypeRef::U32,
+            "i32" => TypeRef::I32,
+            "u64" => TypeRef::U64,
+            _ if value.chars().next().is_some_and(|c| c.is_ascii_uppercase()) => {
+                TypeRef::Named(value)
+            }
+            _ => return Err(format!("unknown type `{value}`")),
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Field {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: TypeRef,
+    #[serde(default)]
+    pub optional: bool,
+    pub doc: Option<String>,
+    /// Field name in Rust when the snake case name doesn't work, like `type`
+    pub rust_name: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EnumValue {
+    pub name: String,
+    /// Only used by number enums
+    pub value: Option<u32>,
+    pub doc: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum TypeKind {
+    /// Numbers with names, like `AppType`
+    NumberEnum { values: Vec<EnumValue> },
+    /// Union of string literals, like `ArtworkKind`
+    StringEnum { values: Vec<EnumValue> },
+    /// Object with named fields
+    Object { fields: Vec<Field> },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeDef {
+    pub name: String,
+    pub doc: Option<String>,
+    #[serde(flatten)]
+    pub kind: TypeKind,
+    /// TypeScript module that already declares this type, it's imported instead of generated
+    pub ts_import: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CommandDef {
+    pub name: String,
+    pub handler: Handler,
+    pub doc: String,
+    pub args: Vec<Field>,
+    pub returns: Vec<Field>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Schema {
+    pub handlers: Vec<HandlerGroup>,
+    pub types: Vec<TypeDef>,
+    pub commands: Vec<CommandDef>,
+}
+
+impl Schema {
+    pub fn command(&self, name: &str) -> Option<&CommandDef> {
+        self.commands.iter().find(|c| c.name == name)
+    }
+
+    pub fn type_def(&self, name: &str) -> Option<&TypeDef> {
+        self.types.iter().find(|t| t.name == name)
+    }
+
+    /// Commands run by `handler`, in schema order
+    pub fn commands_for(&self, handler: Handler) -> impl Iterator<Item = &CommandDef> {
+        self.commands.iter().filter(move |c| c.handler == handler)
+    }
+}
+
+/// Returns the parsed schema.
+pub fn schema() -> &'static Schema {
+    static SCHEMA: OnceLock<Schema> = OnceLock::new();
+    SCHEMA.get_or_init(|| serde_json::from_str(SCHEMA_JSON).expect("commands.json is invalid"))
+}
+
+/// Splits a doc string into lines for a comment block.
+fn doc_lines(doc: &str) -> impl Iterator<Item = &str> {
+    doc.lines().map(str::trim_end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn check_type(ty: &TypeRef, schema: &Schema) {
+        match ty {
+            TypeRef::Array(inner) => check_type(inner, schema),
+            TypeRef::Named(name) => assert!(schema.type_def(name).is_some(), "unknown type {name}"),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn schema_is_consistent() {
+        let schema = schema();
+
+        let mut names = HashSet::new();
+        for command in &schema.commands {
+            assert!(
+                names.insert(&command.name),
+                "duplicate command {}",
+                command.name
+            );
+            for field in command.args.iter().chain(&command.returns) {
+                check_type(&field.ty, schema);
+            }
+        }
+
+        for ty in &schema.types {
+            assert!(names.insert(&ty.name), "duplicate type {}", ty.name);
+            match &ty.kind {
+                TypeKind::NumberEnum { values } => {
+                    assert!(
+                        values.iter().all(|v| v.value.is_some()),
+                        "{} needs values",
+                        ty.name
+                    )
+                }
+                TypeKind::StringEnum { .. } => {}
+                TypeKind::Object { fields } => {
+                    fields.iter().for_each(|f| check_type(&f.ty, schema))
+                }
+            }
+        }
+
+        for handler in [Handler::Steam, Handler::Server] {
+            assert!(schema.handlers.iter().any(|h| h.name == handler));
+        }
+    }
+
+    #[test]
+    fn typescript_is_up_to_date() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../src/js/commands.ts");
+        let current = std::fs::read_to_string(path).unwrap_or_default();
+
+        assert!(
+            current == generate_typescript(schema()),
+            "{TYPESCRIPT_PATH} is out of date, run `cargo run -p conductor-schema`"
+        );
+    }
+}
@@ -0,0 +1,27 @@
+/*
+ *  main.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Writes the TypeScript command types. Run with `cargo run -p conductor-schema` after changing
+//! `commands.json`, the Rust types are generated during the build.
+
+use conductor_schema::{TYPESCRIPT_PATH, generate_typescript, schema};
+use std::path::Path;
+
+fn main() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join(TYPESCRIPT_PATH);
+
+    if let Err(e) = std::fs::write(&path, generate_typescript(schema())) {
+        eprintln!("Failed to write {}: {e}", path.display());
+        std::process::exit(1);
+    }
+
+    println!("Wrote {TYPESCRIPT_PATH}");
+}
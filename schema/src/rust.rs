@@ -0,0 +1,221 @@
+/*
+ *  rust.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::{Field, Schema, TypeDef, TypeKind, TypeRef, doc_lines};
+use std::fmt::Write;
+
+fn rust_type(ty: &TypeRef) -> String {
+    match ty {
+        TypeRef::Bool => "bool".to_owned(),
+        TypeRef::String => "String".to_owned(),
+        TypeRef::U32 => "u32".to_owned(),
+        TypeRef::I32 => "i32".to_owned(),
+        TypeRef::U64 => "u64".to_owned(),
+        TypeRef::Array(inner) => format!("Vec<{}>", rust_type(inner)),
+        TypeRef::Named(name) => name.clone(),
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let mut out = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            out.push('_');
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn pascal_case(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+fn write_doc(out: &mut String, doc: Option<&str>, indent: &str) {
+    let Some(doc) = doc else {
+        return;
+    };
+
+    for (i, line) in doc_lines(doc).enumerate() {
+        // Schema docs are written for TypeScript and start in lower case
+        let line = if i == 0 {
+            pascal_case(line)
+        } else {
+            line.to_owned()
+        };
+
+        if line.is_empty() {
+            _ = writeln!(out, "{indent}///");
+        } else {
+            _ = writeln!(out, "{indent}/// {line}");
+        }
+    }
+}
+
+fn write_fields(out: &mut String, fields: &[Field]) {
+    for field in fields {
+        write_doc(out, field.doc.as_deref(), "    ");
+
+        let name = match &field.rust_name {
+            Some(rust_name) => {
+                _ = writeln!(out, "    #[serde(rename = \"{}\")]", field.name);
+                rust_name.clone()
+            }
+            None => snake_case(&field.name),
+        };
+
+        let ty = rust_type(&field.ty);
+        if field.optional {
+            _ = writeln!(
+                out,
+                "    #[serde(skip_serializing_if = \"Option::is_none\")]"
+            );
+            _ = writeln!(out, "    pub {name}: Option<{ty}>,");
+        } else {
+            _ = writeln!(out, "    pub {name}: {ty},");
+        }
+    }
+}
+
+fn write_struct(out: &mut String, derive: &str, name: &str, fields: &[Field]) {
+    _ = writeln!(out, "#[derive({derive})]");
+    if fields.is_empty() {
+        _ = writeln!(out, "pub struct {name} {{}}\n");
+        return;
+    }
+
+    _ = writeln!(out, "#[serde(rename_all = \"camelCase\")]");
+    _ = writeln!(out, "pub struct {name} {{");
+    write_fields(out, fields);
+    _ = writeln!(out, "}}\n");
+}
+
+fn write_type(out: &mut String, ty: &TypeDef) {
+    write_doc(out, ty.doc.as_deref(), "");
+    let name = &ty.name;
+
+    match &ty.kind {
+        TypeKind::NumberEnum { values } => {
+            _ = writeln!(
+                out,
+                "#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]"
+            );
+            _ = writeln!(out, "#[serde(from = \"u32\", into = \"u32\")]");
+            _ = writeln!(out, "pub enum {name} {{");
+            for value in values {
+                write_doc(out, value.doc.as_deref(), "    ");
+                _ = writeln!(out, "    {},", value.name);
+            }
+            _ = writeln!(
+                out,
+                "    /// Value added to Steam after this crate was released"
+            );
+            _ = writeln!(out, "    Other(u32),");
+            _ = writeln!(out, "}}\n");
+
+            _ = writeln!(out, "impl From<u32> for {name} {{");
+            _ = writeln!(out, "    fn from(value: u32) -> Self {{");
+            _ = writeln!(out, "        match value {{");
+            for value in values {
+                _ = writeln!(
+                    out,
+                    "            {} => {name}::{},",
+                    value.value.unwrap_or_default(),
+                    value.name
+                );
+            }
+            _ = writeln!(out, "            other => {name}::Other(other),");
+            _ = writeln!(out, "        }}\n    }}\n}}\n");
+
+            _ = writeln!(out, "impl From<{name}> for u32 {{");
+            _ = writeln!(out, "    fn from(value: {name}) -> Self {{");
+            _ = writeln!(out, "        match value {{");
+            for value in values {
+                _ = writeln!(
+                    out,
+                    "            {name}::{} => {},",
+                    value.name,
+                    value.value.unwrap_or_default()
+                );
+            }
+            _ = writeln!(out, "            {name}::Other(other) => other,");
+            _ = writeln!(out, "        }}\n    }}\n}}\n");
+        }
+        TypeKind::StringEnum { values } => {
+            _ = writeln!(
+                out,
+                "#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]"
+            );
+            _ = writeln!(out, "#[serde(rename_all = \"camelCase\")]");
+            _ = writeln!(out, "pub enum {name} {{");
+            for value in values {
+                write_doc(out, value.doc.as_deref(), "    ");
+                _ = writeln!(out, "    {},", pascal_case(&value.name));
+            }
+            _ = writeln!(out, "}}\n");
+        }
+        TypeKind::Object { fields } => {
+            write_struct(
+                out,
+                "Serialize, Deserialize, Debug, Clone, PartialEq",
+                name,
+                fields,
+            );
+        }
+    }
+}
+
+/// Generates Rust types for every command. The output expects `Command`, `Empty`, `Serialize`
+/// and `Deserialize` to be in scope.
+pub fn generate_rust(schema: &Schema) -> String {
+    let mut out = String::from("// Generated from schema/commands.json, do not edit.\n\n");
+
+    for ty in &schema.types {
+        write_type(&mut out, ty);
+    }
+
+    for command in &schema.commands {
+        let name = &command.name;
+
+        write_doc(&mut out, Some(&command.doc), "");
+        let derive = if command.args.iter().all(|f| f.optional) {
+            "Serialize, Debug, Clone, Default"
+        } else {
+            "Serialize, Debug, Clone"
+        };
+        write_struct(&mut out, derive, name, &command.args);
+
+        let returns = if command.returns.is_empty() {
+            "Empty".to_owned()
+        } else {
+            let returns = format!("{name}Returns");
+            _ = writeln!(out, "/// Response for [`{name}`]");
+            write_struct(
+                &mut out,
+                "Deserialize, Debug, Clone, PartialEq",
+                &returns,
+                &command.returns,
+            );
+            returns
+        };
+
+        _ = writeln!(out, "impl Command for {name} {{");
+        _ = writeln!(out, "    const NAME: &'static str = \"{name}\";");
+        _ = writeln!(out, "    type Returns = {returns};");
+        _ = writeln!(out, "}}\n");
+    }
+
+    out
+}
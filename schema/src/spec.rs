@@ -0,0 +1,303 @@
+/*
+ *  spec.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::{CommandDef, Field, Handler, Schema, TypeDef, TypeKind, TypeRef};
+use serde_json::{Map, Value, json};
+
+/// Where the server publishes the OpenAPI document
+pub const OPENAPI_PATH: &str = "/.well-known/conductor/openapi.json";
+
+/// Where the server publishes the AsyncAPI document
+pub const ASYNCAPI_PATH: &str = "/.well-known/conductor/asyncapi.json";
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const DESCRIPTION: &str = "Conductor lets you control the Steam client over WebSockets. Connect to `/`, \
+     send one JSON request per message and match responses to requests with `messageId`.";
+
+fn type_schema(ty: &TypeRef) -> Value {
+    match ty {
+        TypeRef::Bool => json!({ "type": "boolean" }),
+        TypeRef::String => json!({ "type": "string" }),
+        TypeRef::U32 => json!({ "type": "integer", "minimum": 0, "maximum": u32::MAX }),
+        TypeRef::I32 => json!({ "type": "integer", "minimum": i32::MIN, "maximum": i32::MAX }),
+        TypeRef::U64 => json!({ "type": "integer", "minimum": 0 }),
+        TypeRef::Array(inner) => json!({ "type": "array", "items": type_schema(inner) }),
+        TypeRef::Named(name) => json!({ "$ref": format!("#/components/schemas/{name}") }),
+    }
+}
+
+fn with_doc(mut schema: Value, doc: Option<&str>) -> Value {
+    if let Some(doc) = doc {
+        schema["description"] = doc.into();
+    }
+    schema
+}
+
+/// Object schema with extra properties that don't come from the command definition
+fn object_schema(fields: &[Field], extra: Map<String, Value>, extra_required: &[&str]) -> Value {
+    let mut properties = extra;
+    let mut required: Vec<&str> = extra_required.to_vec();
+
+    for field in fields {
+        properties.insert(
+            field.name.clone(),
+            with_doc(type_schema(&field.ty), field.doc.as_deref()),
+        );
+        if !field.optional {
+            required.push(&field.name);
+        }
+    }
+
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+fn named_schema(ty: &TypeDef) -> Value {
+    let schema = match &ty.kind {
+        TypeKind::NumberEnum { values } => json!({
+            "type": "integer",
+            "oneOf": values
+                .iter()
+                .map(|v| with_doc(json!({ "const": v.value, "title": v.name }), v.doc.as_deref()))
+                .collect::<Vec<_>>(),
+        }),
+        TypeKind::StringEnum { values } => json!({
+            "type": "string",
+            "enum": values.iter().map(|v| v.name.as_str()).collect::<Vec<_>>(),
+        }),
+        TypeKind::Object { fields } => object_schema(fields, Map::new(), &[]),
+    };
+
+    with_doc(schema, ty.doc.as_deref())
+}
+
+fn message_id_schema() -> Value {
+    with_doc(
+        type_schema(&TypeRef::U32),
+        Some("copied to the response so it can be matched with the request"),
+    )
+}
+
+fn request_schema(command: &CommandDef) -> Value {
+    let mut extra = Map::new();
+    extra.insert("command".to_owned(), json!({ "const": command.name }));
+    extra.insert(
+        "args".to_owned(),
+        object_schema(&command.args, Map::new(), &[]),
+    );
+    extra.insert("messageId".to_owned(), message_id_schema());
+    extra.insert(
+        "secret".to_owned(),
+        json!({ "type": "string", "description": "token, only needed in the first message when auth is enabled" }),
+    );
+
+    object_schema(&[], extra, &["command", "args"])
+}
+
+fn response_schema(command: &CommandDef) -> Value {
+    let mut extra = Map::new();
+    extra.insert("success".to_owned(), json!({ "const": true }));
+    extra.insert("messageId".to_owned(), message_id_schema());
+
+    json!({
+        "oneOf": [
+            object_schema(&command.returns, extra, &["success"]),
+            { "$ref": "#/components/schemas/Failure" },
+        ]
+    })
+}
+
+fn failure_schema() -> Value {
+    json!({
+        "type": "object",
+        "description": "Sent when a command fails",
+        "properties": {
+            "success": { "const": false },
+            "error": { "type": "string", "description": "what went wrong" },
+            "fields": {
+                "type": "array",
+                "description": "one entry for each invalid argument, only set when the arguments are invalid",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "field": { "type": "string", "description": "path to the argument, like `appIds[1]`" },
+                        "error": { "type": "string" },
+                    },
+                    "required": ["field", "error"],
+                },
+            },
+            "messageId": message_id_schema(),
+        },
+        "required": ["success", "error"],
+    })
+}
+
+/// Generates an AsyncAPI 2.6 document describing every WebSocket command. The schemas for
+/// arguments and responses are JSON Schema.
+pub fn generate_asyncapi(schema: &Schema) -> Value {
+    let mut messages = Map::new();
+    let mut schemas = Map::new();
+
+    for ty in &schema.types {
+        schemas.insert(ty.name.clone(), named_schema(ty));
+    }
+    schemas.insert("Failure".to_owned(), failure_schema());
+
+    let mut requests = Vec::new();
+    let mut responses = Vec::new();
+
+    for command in &schema.commands {
+        let name = &command.name;
+        let summary = command.doc.lines().next().unwrap_or_default();
+        let handler = match command.handler {
+            Handler::Steam => "steam",
+            Handler::Server => "server",
+        };
+
+        messages.insert(
+            name.clone(),
+            json!({
+                "name": name,
+                "summary": summary,
+                "description": command.doc,
+                "tags": [{ "name": handler }],
+                "payload": request_schema(command),
+            }),
+        );
+        messages.insert(
+            format!("{name}Response"),
+            json!({
+                "name": format!("{name}Response"),
+                "summary": format!("Response for {name}"),
+                "payload": response_schema(command),
+            }),
+        );
+
+        requests.push(json!({ "$ref": format!("#/components/messages/{name}") }));
+        responses.push(json!({ "$ref": format!("#/components/messages/{name}Response") }));
+    }
+
+    json!({
+        "asyncapi": "2.6.0",
+        "info": {
+            "title": "Conductor",
+            "version": VERSION,
+            "description": DESCRIPTION,
+        },
+        "defaultContentType": "application/json",
+        "channels": {
+            "/": {
+                "description": "WebSocket connection. When auth is enabled the first message needs a `secret`.",
+                "publish": {
+                    "operationId": "sendCommand",
+                    "summary": "Commands sent by the client",
+                    "message": { "oneOf": requests },
+                },
+                "subscribe": {
+                    "operationId": "receiveResponse",
+                    "summary": "Responses sent by the server",
+                    "message": { "oneOf": responses },
+                },
+            },
+        },
+        "components": {
+            "messages": messages,
+            "schemas": schemas,
+        },
+    })
+}
+
+/// Generates an OpenAPI 3.1 document for the HTTP side of the server: the WebSocket upgrade and
+/// the API descriptions.
+pub fn generate_openapi() -> Value {
+    let document = |description: &str| {
+        json!({
+            "get": {
+                "summary": description,
+                "responses": {
+                    "200": {
+                        "description": description,
+                        "content": { "application/json": { "schema": { "type": "object" } } },
+                    },
+                },
+            },
+        })
+    };
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "Conductor",
+            "version": VERSION,
+            "description": DESCRIPTION,
+        },
+        "paths": {
+            "/": {
+                "get": {
+                    "summary": "Open a WebSocket connection",
+                    "description": format!("Commands are sent over the WebSocket, see {ASYNCAPI_PATH} for the messages."),
+                    "responses": {
+                        "101": { "description": "Switching to the WebSocket protocol" },
+                    },
+                },
+            },
+            OPENAPI_PATH: document("This OpenAPI document"),
+            ASYNCAPI_PATH: document("AsyncAPI document with every WebSocket command"),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema;
+
+    #[test]
+    fn asyncapi_has_every_command() {
+        let doc = generate_asyncapi(schema());
+        let messages = doc["components"]["messages"].as_object().unwrap();
+
+        for command in &schema().commands {
+            assert!(messages.contains_key(&command.name));
+            assert!(messages.contains_key(&format!("{}Response", command.name)));
+        }
+
+        let args = &messages["InstallApps"]["payload"]["properties"]["args"];
+        assert_eq!(args["required"], json!(["appIds"]));
+        assert_eq!(
+            args["properties"]["appIds"]["items"],
+            json!({ "type": "integer", "minimum": 0, "maximum": u32::MAX })
+        );
+    }
+
+    #[test]
+    fn refs_point_to_schemas() {
+        fn check(value: &Value, doc: &Value) {
+            match value {
+                Value::Object(map) => {
+                    if let Some(Value::String(path)) = map.get("$ref") {
+                        let pointer = path.trim_start_matches('#');
+                        assert!(doc.pointer(pointer).is_some(), "broken $ref {path}");
+                    }
+                    map.values().for_each(|v| check(v, doc));
+                }
+                Value::Array(items) => items.iter().for_each(|v| check(v, doc)),
+                _ => {}
+            }
+        }
+
+        let doc = generate_asyncapi(schema());
+        check(&doc, &doc);
+    }
+}
@@ -0,0 +1,166 @@
+/*
+ *  typescript.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::{Field, Schema, TypeDef, TypeKind, TypeRef, doc_lines};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+fn ts_type(ty: &TypeRef) -> String {
+    match ty {
+        TypeRef::Bool => "boolean".to_owned(),
+        TypeRef::String => "string".to_owned(),
+        TypeRef::U32 | TypeRef::I32 | TypeRef::U64 => "number".to_owned(),
+        TypeRef::Array(inner) => format!("{}[]", ts_type(inner)),
+        TypeRef::Named(name) => name.clone(),
+    }
+}
+
+fn write_doc(out: &mut String, doc: Option<&str>, indent: &str) {
+    let Some(doc) = doc else {
+        return;
+    };
+
+    if !doc.contains('\n') {
+        _ = writeln!(out, "{indent}/** {doc} */");
+        return;
+    }
+
+    _ = writeln!(out, "{indent}/**");
+    for line in doc_lines(doc) {
+        if line.is_empty() {
+            _ = writeln!(out, "{indent} *");
+        } else {
+            _ = writeln!(out, "{indent} * {line}");
+        }
+    }
+    _ = writeln!(out, "{indent} */");
+}
+
+/// Block comments like the ones in the rest of the TypeScript code
+fn write_block_doc(out: &mut String, doc: &str, indent: &str) {
+    _ = writeln!(out, "{indent}/**");
+    for line in doc_lines(doc) {
+        if line.is_empty() {
+            _ = writeln!(out, "{indent} *");
+        } else {
+            _ = writeln!(out, "{indent} * {line}");
+        }
+    }
+    _ = writeln!(out, "{indent} */");
+}
+
+fn write_fields(out: &mut String, fields: &[Field], indent: &str) {
+    for field in fields {
+        write_doc(out, field.doc.as_deref(), indent);
+        let optional = if field.optional { "?" } else { "" };
+        _ = writeln!(
+            out,
+            "{indent}{}{optional}: {};",
+            field.name,
+            ts_type(&field.ty)
+        );
+    }
+}
+
+fn write_type(out: &mut String, ty: &TypeDef) {
+    if let Some(doc) = &ty.doc {
+        write_block_doc(out, doc, "");
+    }
+    let name = &ty.name;
+
+    match &ty.kind {
+        TypeKind::NumberEnum { values } => {
+            _ = writeln!(out, "export enum {name} {{");
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    _ = writeln!(out);
+                }
+                write_doc(out, value.doc.as_deref(), "    ");
+                _ = writeln!(
+                    out,
+                    "    {} = {},",
+                    value.name,
+                    value.value.unwrap_or_default()
+                );
+            }
+            _ = writeln!(out, "}}\n");
+        }
+        TypeKind::StringEnum { values } => {
+            let union = values
+                .iter()
+                .map(|v| format!("\"{}\"", v.name))
+                .collect::<Vec<_>>()
+                .join(" | ");
+            _ = writeln!(out, "export type {name} = {union};\n");
+        }
+        TypeKind::Object { fields } => {
+            _ = writeln!(out, "export interface {name} {{");
+            write_fields(out, fields, "    ");
+            _ = writeln!(out, "}}\n");
+        }
+    }
+}
+
+fn write_object(out: &mut String, key: &str, fields: &[Field]) {
+    if fields.is_empty() {
+        _ = writeln!(out, "        {key}: {{}};");
+        return;
+    }
+
+    _ = writeln!(out, "        {key}: {{");
+    write_fields(out, fields, "            ");
+    _ = writeln!(out, "        }};");
+}
+
+/// Generates `src/js/commands.ts` with one type per handler group listing its commands.
+pub fn generate_typescript(schema: &Schema) -> String {
+    let mut out = String::from(
+        "/*\n *  commands.ts\n *  Generated from schema/commands.json by `cargo run -p conductor-schema`, do not edit.\n */\n\n",
+    );
+
+    let mut imports = BTreeMap::<&str, Vec<&str>>::new();
+    for ty in &schema.types {
+        if let Some(module) = &ty.ts_import {
+            imports.entry(module).or_default().push(&ty.name);
+        }
+    }
+    for (module, mut names) in imports {
+        names.sort();
+        _ = writeln!(
+            out,
+            "import type {{ {} }} from \"{module}\";",
+            names.join(", ")
+        );
+    }
+    _ = writeln!(out);
+
+    for group in &schema.handlers {
+        write_block_doc(&mut out, &group.doc, "");
+        _ = writeln!(out, "export type {} = {{", group.type_name);
+
+        for command in schema.commands_for(group.name) {
+            write_block_doc(&mut out, &command.doc, "    ");
+            _ = writeln!(out, "    {}: {{", command.name);
+            write_object(&mut out, "args", &command.args);
+            write_object(&mut out, "returns", &command.returns);
+            _ = writeln!(out, "    }};");
+        }
+
+        _ = writeln!(out, "}};\n");
+    }
+
+    for ty in schema.types.iter().filter(|t| t.ts_import.is_none()) {
+        write_type(&mut out, ty);
+    }
+
+    // Drop the blank line after the last type
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
@@ -0,0 +1,231 @@
+/*
+ *  validate.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::{CommandDef, Field, Schema, TypeKind, TypeRef};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Problem with one argument
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    /// Path to the argument, like `appIds[1]`
+    pub field: String,
+    pub error: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.field, self.error)
+    }
+}
+
+impl Schema {
+    /// Checks `args` against the arguments of `command`. Returns every problem found, or an
+    /// empty list if the arguments are valid.
+    pub fn validate_args(&self, command: &CommandDef, args: &Value) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        match args {
+            Value::Object(args) => self.check_fields(&command.args, args, "", &mut errors),
+            _ => errors.push(FieldError {
+                field: "args".to_owned(),
+                error: "must be an object".to_owned(),
+            }),
+        }
+
+        errors
+    }
+
+    fn check_fields(
+        &self,
+        fields: &[Field],
+        object: &Map<String, Value>,
+        prefix: &str,
+        errors: &mut Vec<FieldError>,
+    ) {
+        for field in fields {
+            let path = format!("{prefix}{}", field.name);
+
+            match object.get(&field.name) {
+                None | Some(Value::Null) if field.optional => {}
+                None | Some(Value::Null) => errors.push(FieldError {
+                    field: path,
+                    error: "is required".to_owned(),
+                }),
+                Some(value) => self.check_value(&field.ty, value, path, errors),
+            }
+        }
+
+        for key in object.keys() {
+            if !fields.iter().any(|f| &f.name == key) {
+                errors.push(FieldError {
+                    field: format!("{prefix}{key}"),
+                    error: "is not a known argument".to_owned(),
+                });
+            }
+        }
+    }
+
+    fn check_value(&self, ty: &TypeRef, value: &Value, path: String, errors: &mut Vec<FieldError>) {
+        let error = match ty {
+            TypeRef::Bool if !value.is_boolean() => "must be a boolean".to_owned(),
+            TypeRef::String if !value.is_string() => "must be a string".to_owned(),
+            TypeRef::U32 if value.as_u64().is_none_or(|n| u32::try_from(n).is_err()) => {
+                format!("must be a whole number from 0 to {}", u32::MAX)
+            }
+            TypeRef::I32 if value.as_i64().is_none_or(|n| i32::try_from(n).is_err()) => {
+                format!("must be a whole number from {} to {}", i32::MIN, i32::MAX)
+            }
+            TypeRef::U64 if !value.is_u64() => "must be a positive whole number".to_owned(),
+            TypeRef::Array(inner) => {
+                let Some(items) = value.as_array() else {
+                    errors.push(FieldError {
+                        field: path,
+                        error: "must be an array".to_owned(),
+                    });
+                    return;
+                };
+
+                for (i, item) in items.iter().enumerate() {
+                    self.check_value(inner, item, format!("{path}[{i}]"), errors);
+                }
+                return;
+            }
+            TypeRef::Named(name) => match self.type_def(name).map(|t| &t.kind) {
+                Some(TypeKind::NumberEnum { values }) => {
+                    let n = value.as_u64().and_then(|n| u32::try_from(n).ok());
+                    if n.is_some() && values.iter().any(|v| v.value == n) {
+                        return;
+                    }
+
+                    let names: Vec<_> = values
+                        .iter()
+                        .map(|v| format!("{} ({})", v.value.unwrap_or_default(), v.name))
+                        .collect();
+                    format!("must be a valid {name}: {}", names.join(", "))
+                }
+                Some(TypeKind::StringEnum { values }) => {
+                    if value
+                        .as_str()
+                        .is_some_and(|s| values.iter().any(|v| v.name == s))
+                    {
+                        return;
+                    }
+
+                    let names: Vec<_> = values.iter().map(|v| format!("\"{}\"", v.name)).collect();
+                    format!("must be one of {}", names.join(", "))
+                }
+                Some(TypeKind::Object { fields }) => {
+                    match value.as_object() {
+                        Some(object) => {
+                            self.check_fields(fields, object, &format!("{path}."), errors)
+                        }
+                        None => errors.push(FieldError {
+                            field: path,
+                            error: "must be an object".to_owned(),
+                        }),
+                    }
+                    return;
+                }
+                None => format!("has unknown type {name}"),
+            },
+            _ => return,
+        };
+
+        errors.push(FieldError { field: path, error });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schema;
+    use serde_json::json;
+
+    fn validate(command: &str, args: serde_json::Value) -> Vec<String> {
+        let schema = schema();
+        schema
+            .validate_args(schema.command(command).unwrap(), &args)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn valid_args() {
+        assert!(validate("GetUIMode", json!({})).is_empty());
+        assert!(validate("InstallApps", json!({ "appIds": [10, 20], "folderIdx": 1 })).is_empty());
+        assert!(validate("GetApps", json!({ "typeFilter": [1, 1073741824] })).is_empty());
+        assert!(
+            validate(
+                "SetArtwork",
+                json!({ "appId": 1, "kind": "hero", "path": "a.png" })
+            )
+            .is_empty()
+        );
+        assert!(validate("RemoveArtwork", json!({ "appId": 1, "kind": null })).is_empty());
+    }
+
+    #[test]
+    fn missing_and_unknown_fields() {
+        assert_eq!(
+            validate("AddShortcut", json!({ "name": "Game", "exec": "game.exe" })),
+            ["exe is required", "exec is not a known argument"]
+        );
+        assert_eq!(validate("RunApp", json!([])), ["args must be an object"]);
+    }
+
+    #[test]
+    fn wrong_types() {
+        assert_eq!(
+            validate(
+                "InstallApps",
+                json!({ "appIds": [10, "20", -1], "folderIdx": true })
+            ),
+            [
+                "appIds[1] must be a whole number from 0 to 4294967295",
+                "appIds[2] must be a whole number from 0 to 4294967295",
+                "folderIdx must be a whole number from 0 to 4294967295",
+            ]
+        );
+        assert_eq!(
+            validate("UninstallApps", json!({ "appIds": 10 })),
+            ["appIds must be an array"]
+        );
+        assert_eq!(
+            validate("UninstallApp", json!({ "appId": 10, "autoConfirm": "yes" })),
+            ["autoConfirm must be a boolean"]
+        );
+    }
+
+    #[test]
+    fn enum_values() {
+        assert_eq!(
+            validate("GetApps", json!({ "typeFilter": [1, 3] })),
+            [
+                "typeFilter[1] must be a valid AppType: 1 (Game), 2 (Software), 4 (Tool), 8 (Demo), \
+                 256 (ClientFeature), 65536 (Playtest), 1073741824 (Shortcut)"
+            ]
+        );
+        assert_eq!(
+            validate(
+                "SetArtwork",
+                json!({ "appId": 1, "kind": "banner", "path": "a.png" })
+            ),
+            [r#"kind must be one of "grid", "wideCapsule", "hero", "logo""#]
+        );
+        assert!(
+            validate(
+                "GetInstallFolders",
+                json!({ "fields": ["driveName", "size"] })
+            )[0]
+            .starts_with("fields[1] must be one of \"index\"")
+        );
+    }
+}
@@ -0,0 +1,170 @@
+/*
+ *  api_spec.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use clap::ValueEnum;
+use conductor_schema::{ASYNCAPI_PATH, OPENAPI_PATH, generate_asyncapi, generate_openapi, schema};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Longest request head we read before giving up on a description request
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// How long a client gets to send the request line
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum SpecFormat {
+    /// OpenAPI document for the HTTP endpoints
+    Openapi,
+    /// AsyncAPI document with every WebSocket command
+    Asyncapi,
+}
+
+impl SpecFormat {
+    fn from_path(path: &str) -> Option<Self> {
+        match path.split('?').next()? {
+            OPENAPI_PATH => Some(SpecFormat::Openapi),
+            ASYNCAPI_PATH => Some(SpecFormat::Asyncapi),
+            _ => None,
+        }
+    }
+
+    /// Generates the document as pretty printed JSON.
+    pub fn generate(self) -> String {
+        let doc = match self {
+            SpecFormat::Openapi => generate_openapi(),
+            SpecFormat::Asyncapi => generate_asyncapi(schema()),
+        };
+        serde_json::to_string_pretty(&doc).unwrap_or_default()
+    }
+}
+
+/// Waits for the request line and returns the document it asks for, without consuming anything
+/// so WebSocket handshakes can still go through. Never returns if the client sends nothing, the
+/// caller puts a timeout on it.
+async fn requested_spec(stream: &TcpStream) -> Option<SpecFormat> {
+    let mut buf = [0; MAX_REQUEST_SIZE];
+
+    loop {
+        let n = stream.peek(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+
+        if let Some(end) = buf[..n].windows(2).position(|w| w == b"\r\n") {
+            let line = std::str::from_utf8(&buf[..end]).ok()?;
+            let mut parts = line.split(' ');
+            return match parts.next() {
+                Some("GET") => SpecFormat::from_path(parts.next()?),
+                _ => None,
+            };
+        }
+
+        if n == buf.len() {
+            return None;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// Answers plain HTTP requests for the API description documents. Returns `true` if the request
+/// was handled, or the client didn't send one in time, and the connection should be dropped.
+pub async fn try_serve(stream: &mut TcpStream) -> bool {
+    let format = match tokio::time::timeout(REQUEST_TIMEOUT, requested_spec(stream)).await {
+        Ok(Some(format)) => format,
+        Ok(None) => return false,
+        Err(_) => {
+            log::debug!("Client did not send a request in time");
+            return true;
+        }
+    };
+
+    // Read the whole request head so the client doesn't see a reset
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    let read_head = async {
+        while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_REQUEST_SIZE {
+            match stream.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => head.extend_from_slice(&buf[..n]),
+            }
+        }
+    };
+    _ = tokio::time::timeout(REQUEST_TIMEOUT, read_head).await;
+
+    let body = format.generate();
+    let response = format!(
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    );
+
+    if let Err(e) = stream.write_all(response.as_bytes()).await {
+        log::debug!("Failed to send API description: {e}");
+    }
+    _ = stream.shutdown().await;
+
+    log::debug!("Sent {format:?} description");
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    async fn connect(request: &str) -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        client.write_all(request.as_bytes()).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        (client, server)
+    }
+
+    #[tokio::test]
+    async fn serves_description() {
+        let (mut client, mut server) =
+            connect("GET /.well-known/conductor/asyncapi.json HTTP/1.1\r\nHost: x\r\n\r\n").await;
+        assert!(try_serve(&mut server).await);
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+
+        assert!(head.starts_with("HTTP/1.1 200 OK"));
+        let doc: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(doc["asyncapi"], "2.6.0");
+    }
+
+    #[tokio::test]
+    async fn leaves_websocket_handshakes_alone() {
+        let request = "GET / HTTP/1.1\r\nUpgrade: websocket\r\n\r\n";
+        let (_client, mut server) = connect(request).await;
+        assert!(!try_serve(&mut server).await);
+
+        let mut buf = vec![0; request.len()];
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, request.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn drops_silent_clients() {
+        for request in ["", "GET /.well-known"] {
+            let (_client, mut server) = connect(request).await;
+            let served = tokio::time::timeout(REQUEST_TIMEOUT * 2, try_serve(&mut server)).await;
+            assert_eq!(served, Ok(true));
+        }
+    }
+}
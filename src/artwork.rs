@@ -0,0 +1,562 @@
+/*
+ *  artwork.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::steam::{Steam, SteamError};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Biggest image file we accept. Steam's own uploads are limited to a few megabytes, this leaves
+/// plenty of room for 4K heroes.
+const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Biggest width or height we accept
+const MAX_DIMENSION: u32 = 8192;
+
+/// Library image slots, each one maps to a file name in `userdata/<accountid>/config/grid/`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum ArtworkKind {
+    /// Vertical capsule shown in the library grid (600x900)
+    Grid,
+    /// Horizontal capsule shown in recent games (920x430)
+    WideCapsule,
+    /// Banner at the top of the game page (1920x620)
+    Hero,
+    /// Transparent logo drawn over the hero
+    Logo,
+}
+
+impl ArtworkKind {
+    const ALL: [ArtworkKind; 4] = [
+        ArtworkKind::Grid,
+        ArtworkKind::WideCapsule,
+        ArtworkKind::Hero,
+        ArtworkKind::Logo,
+    ];
+
+    fn file_stem(self, app_id: u32) -> String {
+        match self {
+            ArtworkKind::Grid => format!("{app_id}p"),
+            ArtworkKind::WideCapsule => format!("{app_id}"),
+            ArtworkKind::Hero => format!("{app_id}_hero"),
+            ArtworkKind::Logo => format!("{app_id}_logo"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ImageType {
+    Png,
+    Jpeg,
+}
+
+impl ImageType {
+    const ALL: [ImageType; 2] = [ImageType::Png, ImageType::Jpeg];
+
+    fn extension(self) -> &'static str {
+        match self {
+            ImageType::Png => "png",
+            ImageType::Jpeg => "jpg",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ArtworkError {
+    #[error("{0}")]
+    Steam(SteamError),
+    #[error("Could not read image: {0}")]
+    ReadImage(std::io::Error),
+    #[error("Image is {0} bytes, the limit is {MAX_FILE_SIZE}")]
+    TooLarge(u64),
+    #[error("Unsupported image type, use PNG or JPEG")]
+    UnsupportedType,
+    #[error("Image is corrupted or truncated")]
+    InvalidImage,
+    #[error("Image is {0}x{1}, width and height must be between 1 and {MAX_DIMENSION}")]
+    InvalidSize(u32, u32),
+    #[error("Only regular files can be linked")]
+    NotAFile,
+    #[error("Could not write artwork: {0}")]
+    WriteFile(std::io::Error),
+}
+
+#[derive(Debug, PartialEq)]
+struct ImageInfo {
+    image_type: ImageType,
+    width: u32,
+    height: u32,
+}
+
+fn png_info(bytes: &[u8]) -> Option<ImageInfo> {
+    // Signature is followed by the IHDR chunk with width and height as big endian u32
+    if bytes.get(12..16)? != b"IHDR" {
+        return None;
+    }
+
+    Some(ImageInfo {
+        image_type: ImageType::Png,
+        width: u32::from_be_bytes(bytes.get(16..20)?.try_into().ok()?),
+        height: u32::from_be_bytes(bytes.get(20..24)?.try_into().ok()?),
+    })
+}
+
+fn jpeg_info(bytes: &[u8]) -> Option<ImageInfo> {
+    let mut pos = 2;
+
+    // Walk the segments until we find a start of frame marker
+    loop {
+        if *bytes.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(pos + 1)?;
+        pos += 2;
+
+        match marker {
+            // Fill bytes
+            0xFF => pos -= 1,
+            // Markers without a length
+            0x01 | 0xD0..=0xD7 => {}
+            // End of image before any frame
+            0xD9 => return None,
+            _ => {
+                let len = u16::from_be_bytes(bytes.get(pos..pos + 2)?.try_into().ok()?) as usize;
+
+                if matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+                    return Some(ImageInfo {
+                        image_type: ImageType::Jpeg,
+                        height: u16::from_be_bytes(bytes.get(pos + 3..pos + 5)?.try_into().ok()?)
+                            as u32,
+                        width: u16::from_be_bytes(bytes.get(pos + 5..pos + 7)?.try_into().ok()?)
+                            as u32,
+                    });
+                }
+
+                pos += len;
+            }
+        }
+    }
+}
+
+fn image_info(path: &Path) -> Result<ImageInfo, ArtworkError> {
+    let size = std::fs::metadata(path)
+        .map_err(ArtworkError::ReadImage)?
+        .len();
+    if size > MAX_FILE_SIZE {
+        return Err(ArtworkError::TooLarge(size));
+    }
+
+    let bytes = std::fs::read(path).map_err(ArtworkError::ReadImage)?;
+
+    let info = if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_info(&bytes)
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        jpeg_info(&bytes)
+    } else {
+        return Err(ArtworkError::UnsupportedType);
+    };
+
+    let Some(info) = info else {
+        return Err(ArtworkError::InvalidImage);
+    };
+
+    if !(1..=MAX_DIMENSION).contains(&info.width) || !(1..=MAX_DIMENSION).contains(&info.height) {
+        return Err(ArtworkError::InvalidSize(info.width, info.height));
+    }
+
+    Ok(info)
+}
+
+/// Returns the grid folder for `account_id`, or for the most recent user if it's not set.
+pub fn grid_dir(steam: &Steam, account_id: Option<u32>) -> Result<PathBuf, ArtworkError> {
+    let user = steam.user(account_id).map_err(ArtworkError::Steam)?;
+    Ok(steam.grid_dir(user.account_id))
+}
+
+/// Artwork file found for an app
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Artwork {
+    pub kind: ArtworkKind,
+    pub path: PathBuf,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+fn existing_files(grid_dir: &Path, app_id: u32, kind: ArtworkKind) -> Vec<PathBuf> {
+    ImageType::ALL
+        .iter()
+        .map(|t| grid_dir.join(format!("{}.{}", kind.file_stem(app_id), t.extension())))
+        .filter(|p| p.symlink_metadata().is_ok())
+        .collect()
+}
+
+/// Lists all the artwork set for `app_id`.
+pub fn list_artwork(grid_dir: &Path, app_id: u32) -> Vec<Artwork> {
+    ArtworkKind::ALL
+        .iter()
+        .flat_map(|kind| {
+            existing_files(grid_dir, app_id, *kind)
+                .into_iter()
+                .map(|path| {
+                    let info = image_info(&path).ok();
+                    Artwork {
+                        kind: *kind,
+                        width: info.as_ref().map(|i| i.width),
+                        height: info.as_ref().map(|i| i.height),
+                        path,
+                    }
+                })
+        })
+        .collect()
+}
+
+/// Removes artwork of one kind, or all of it if `kind` is `None`. Returns the deleted files.
+pub fn remove_artwork(
+    grid_dir: &Path,
+    app_id: u32,
+    kind: Option<ArtworkKind>,
+) -> Result<Vec<PathBuf>, ArtworkError> {
+    let kinds = match kind {
+        Some(kind) => vec![kind],
+        None => ArtworkKind::ALL.to_vec(),
+    };
+
+    let mut removed = Vec::new();
+    for kind in kinds {
+        for path in existing_files(grid_dir, app_id, kind) {
+            std::fs::remove_file(&path).map_err(ArtworkError::WriteFile)?;
+            removed.push(path);
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Copies or links `source` into the grid folder, replacing any existing artwork of the same kind.
+/// Returns the new file's path.
+///
+/// Links point at the file `source` resolves to now, which must be a regular file. The image is
+/// only checked once, so whoever can write to that file can change the artwork later.
+pub fn set_artwork(
+    grid_dir: &Path,
+    app_id: u32,
+    kind: ArtworkKind,
+    source: &Path,
+    link: bool,
+) -> Result<PathBuf, ArtworkError> {
+    // Check the file the link will point at, not a symlink that could be swapped out afterwards
+    let source = if link {
+        let source = std::fs::canonicalize(source).map_err(ArtworkError::ReadImage)?;
+        if !source
+            .metadata()
+            .map_err(ArtworkError::ReadImage)?
+            .is_file()
+        {
+            return Err(ArtworkError::NotAFile);
+        }
+        source
+    } else {
+        source.to_path_buf()
+    };
+    let info = image_info(&source)?;
+
+    std::fs::create_dir_all(grid_dir).map_err(ArtworkError::WriteFile)?;
+
+    let extension = info.image_type.extension();
+    let target = grid_dir.join(format!("{}.{extension}", kind.file_stem(app_id)));
+
+    // Write next to the target first so the old artwork stays if this fails
+    let tmp_path = target.with_extension(format!("{extension}.tmp"));
+    _ = std::fs::remove_file(&tmp_path);
+
+    let result = if link {
+        cfg_if::cfg_if! {
+            if #[cfg(unix)] {
+                std::os::unix::fs::symlink(source, &tmp_path)
+            } else {
+                // Symlinks need admin rights on Windows, hard links don't
+                std::fs::hard_link(source, &tmp_path)
+            }
+        }
+    } else {
+        std::fs::copy(&source, &tmp_path).map(|_| ())
+    };
+
+    if let Err(e) = result.and_then(|_| std::fs::rename(&tmp_path, &target)) {
+        _ = std::fs::remove_file(&tmp_path);
+        return Err(ArtworkError::WriteFile(e));
+    }
+
+    // Remove the old artwork if it was saved in another format
+    for path in existing_files(grid_dir, app_id, kind) {
+        if path != target {
+            std::fs::remove_file(&path).map_err(ArtworkError::WriteFile)?;
+        }
+    }
+
+    Ok(target)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetArgs {
+    app_id: u32,
+    account_id: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetArgs {
+    app_id: u32,
+    account_id: Option<u32>,
+    kind: ArtworkKind,
+    path: PathBuf,
+    #[serde(default)]
+    link: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoveArgs {
+    app_id: u32,
+    account_id: Option<u32>,
+    kind: Option<ArtworkKind>,
+}
+
+fn run_command(
+    steam: &Steam,
+    command: &str,
+    args: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let parse_err = |e: serde_json::Error| format!("Invalid arguments: {e}");
+
+    match command {
+        "GetArtwork" => {
+            let args = GetArgs::deserialize(args).map_err(parse_err)?;
+            let dir = grid_dir(steam, args.account_id).map_err(|e| e.to_string())?;
+
+            Ok(json!({ "artwork": list_artwork(&dir, args.app_id) }))
+        }
+        "SetArtwork" => {
+            let args = SetArgs::deserialize(args).map_err(parse_err)?;
+            let dir = grid_dir(steam, args.account_id).map_err(|e| e.to_string())?;
+            let path = set_artwork(&dir, args.app_id, args.kind, &args.path, args.link)
+                .map_err(|e| e.to_string())?;
+
+            log::info!("Set {:?} artwork for app {}", args.kind, args.app_id);
+            Ok(json!({ "path": path }))
+        }
+        "RemoveArtwork" => {
+            let args = RemoveArgs::deserialize(args).map_err(parse_err)?;
+            let dir = grid_dir(steam, args.account_id).map_err(|e| e.to_string())?;
+            let removed =
+                remove_artwork(&dir, args.app_id, args.kind).map_err(|e| e.to_string())?;
+
+            Ok(json!({ "removed": removed }))
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Runs an artwork command and builds the response sent to the client.
+pub fn handle_command(steam: &Steam, command: &str, args: &serde_json::Value) -> serde_json::Value {
+    match run_command(steam, command, args) {
+        Ok(mut res) => {
+            res["success"] = true.into();
+            res
+        }
+        Err(error) => json!({
+            "success": false,
+            "error": error,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        bytes.extend(width.to_be_bytes());
+        bytes.extend(height.to_be_bytes());
+        bytes.extend([8, 6, 0, 0, 0]);
+        bytes
+    }
+
+    /// JPEG with an APP0 segment before the frame marker
+    fn jpeg(marker: u8, width: u16, height: u16) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x4A, 0x46];
+        bytes.extend([0xFF, marker, 0x00, 0x11, 0x08]);
+        bytes.extend(height.to_be_bytes());
+        bytes.extend(width.to_be_bytes());
+        bytes.extend([0x03; 10]);
+        bytes
+    }
+
+    fn write(dir: &Path, name: &str, bytes: &[u8]) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_image_sizes() {
+        assert_eq!(
+            png_info(&png(600, 900)),
+            Some(ImageInfo {
+                image_type: ImageType::Png,
+                width: 600,
+                height: 900,
+            })
+        );
+
+        // Baseline and progressive frames, plus fill bytes before a marker
+        for marker in [0xC0, 0xC2] {
+            let info = jpeg_info(&jpeg(marker, 1920, 620)).unwrap();
+            assert_eq!((info.width, info.height), (1920, 620));
+        }
+        let mut filled = jpeg(0xC1, 920, 430);
+        filled.insert(8, 0xFF);
+        assert_eq!(jpeg_info(&filled).map(|i| i.width), Some(920));
+
+        // Huffman and arithmetic coding tables use SOF-like markers but aren't frames
+        for marker in [0xC4, 0xC8, 0xCC] {
+            assert_eq!(jpeg_info(&jpeg(marker, 1, 1)), None);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_images() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let png = png(600, 900);
+        for len in [0, 8, 15, 20, 23] {
+            assert_eq!(png_info(&png[..len]), None);
+        }
+        let jpeg = jpeg(0xC0, 600, 900);
+        for len in [2, 3, 7, 12, 16] {
+            assert_eq!(jpeg_info(&jpeg[..len]), None);
+        }
+        assert_eq!(jpeg_info(&[0xFF, 0xD8, 0xFF, 0xD9]), None);
+        assert_eq!(jpeg_info(&[0xFF, 0xD8, 0x00, 0x00]), None);
+
+        let garbage = write(dir.path(), "garbage", b"GIF89a not an image");
+        assert!(matches!(
+            image_info(&garbage),
+            Err(ArtworkError::UnsupportedType)
+        ));
+        let truncated = write(dir.path(), "truncated", &png[..20]);
+        assert!(matches!(
+            image_info(&truncated),
+            Err(ArtworkError::InvalidImage)
+        ));
+        assert!(matches!(
+            image_info(&dir.path().join("missing")),
+            Err(ArtworkError::ReadImage(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_oversized_images() {
+        let dir = tempfile::tempdir().unwrap();
+
+        for (width, height) in [(MAX_DIMENSION + 1, 900), (600, 0)] {
+            let path = write(dir.path(), "big.png", &png(width, height));
+            assert!(matches!(
+                image_info(&path),
+                Err(ArtworkError::InvalidSize(w, h)) if (w, h) == (width, height)
+            ));
+        }
+        let path = write(dir.path(), "big.jpg", &jpeg(0xC0, u16::MAX, 10));
+        assert!(matches!(
+            image_info(&path),
+            Err(ArtworkError::InvalidSize(65535, 10))
+        ));
+
+        let path = write(dir.path(), "huge.png", &png(600, 900));
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(MAX_FILE_SIZE + 1)
+            .unwrap();
+        assert!(matches!(
+            image_info(&path),
+            Err(ArtworkError::TooLarge(size)) if size == MAX_FILE_SIZE + 1
+        ));
+    }
+
+    #[test]
+    fn names_files_like_steam() {
+        let dir = tempfile::tempdir().unwrap();
+        let grid = dir.path().join("grid");
+        let png = write(dir.path(), "art.png", &png(600, 900));
+        let jpeg = write(dir.path(), "art.jpg", &jpeg(0xC0, 600, 900));
+
+        for (kind, name) in [
+            (ArtworkKind::Grid, "440p.png"),
+            (ArtworkKind::WideCapsule, "440.png"),
+            (ArtworkKind::Hero, "440_hero.png"),
+            (ArtworkKind::Logo, "440_logo.png"),
+        ] {
+            let path = set_artwork(&grid, 440, kind, &png, false).unwrap();
+            assert_eq!(path, grid.join(name));
+        }
+        assert_eq!(list_artwork(&grid, 440).len(), 4);
+
+        // A JPEG replaces the PNG of the same kind
+        let path = set_artwork(&grid, 440, ArtworkKind::Hero, &jpeg, false).unwrap();
+        assert_eq!(path, grid.join("440_hero.jpg"));
+        assert!(!grid.join("440_hero.png").exists());
+
+        let removed = remove_artwork(&grid, 440, None).unwrap();
+        assert_eq!(removed.len(), 4);
+        assert!(list_artwork(&grid, 440).is_empty());
+    }
+
+    #[test]
+    fn replaces_existing_artwork() {
+        let dir = tempfile::tempdir().unwrap();
+        let grid = dir.path().join("grid");
+        let png = write(dir.path(), "art.png", &png(600, 900));
+        let jpeg = write(dir.path(), "art.jpg", &jpeg(0xC0, 600, 900));
+
+        let first = set_artwork(&grid, 440, ArtworkKind::Grid, &png, false).unwrap();
+        let second = set_artwork(&grid, 440, ArtworkKind::Grid, &jpeg, false).unwrap();
+
+        assert!(!first.exists());
+        assert_eq!(existing_files(&grid, 440, ArtworkKind::Grid), [second]);
+        assert_eq!(std::fs::read_dir(&grid).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_the_resolved_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let grid = dir.path().join("grid");
+        let image = write(dir.path(), "art.png", &png(600, 900));
+        let alias = dir.path().join("alias.png");
+        std::os::unix::fs::symlink(&image, &alias).unwrap();
+
+        let path = set_artwork(&grid, 440, ArtworkKind::Grid, &alias, true).unwrap();
+        assert_eq!(
+            std::fs::read_link(path).unwrap(),
+            std::fs::canonicalize(&image).unwrap()
+        );
+
+        assert!(matches!(
+            set_artwork(&grid, 440, ArtworkKind::Grid, dir.path(), true),
+            Err(ArtworkError::NotAFile)
+        ));
+    }
+}
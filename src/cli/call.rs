@@ -0,0 +1,172 @@
+/*
+ *  cli/call.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::cli::ConnectionArgs;
+use clap::Args;
+use conductor_client::{Client, ClientOptions};
+use std::time::Duration;
+
+#[derive(Args)]
+pub struct CallArgs {
+    /// Command to run, like `RunApp`
+    command: String,
+    /// Argument as `key=value`, can be repeated. Values are parsed as JSON if possible so
+    /// `appId=730` sends a number and `name=Portal` a string
+    #[arg(short, long = "arg", value_name = "KEY=VALUE")]
+    args: Vec<String>,
+    /// Arguments as a JSON object, merged with the ones from `--arg`
+    #[arg(long, value_name = "OBJECT")]
+    json: Option<String>,
+    /// Seconds to wait for a response
+    #[arg(long, default_value_t = 30)]
+    timeout: u64,
+    #[command(flatten)]
+    connection: ConnectionArgs,
+}
+
+/// Parses a `key=value` argument, reading the value as JSON when it's valid JSON.
+pub fn parse_arg(arg: &str) -> Option<(String, serde_json::Value)> {
+    let (key, value) = arg.split_once('=')?;
+    let value =
+        serde_json::from_str(value).unwrap_or_else(|_| serde_json::Value::String(value.to_owned()));
+
+    Some((key.to_owned(), value))
+}
+
+/// Builds the arguments object from `--json` and the `key=value` pairs. Pairs override keys from
+/// `--json`, and later pairs override earlier ones.
+fn build_args(json: Option<&str>, pairs: &[String]) -> Result<serde_json::Value, String> {
+    let mut args = match json {
+        Some(json) => match serde_json::from_str::<serde_json::Value>(json) {
+            Ok(serde_json::Value::Object(map)) => map,
+            Ok(_) => return Err("--json must be an object".to_owned()),
+            Err(e) => return Err(format!("Invalid --json: {e}")),
+        },
+        None => serde_json::Map::new(),
+    };
+
+    for arg in pairs {
+        let Some((key, value)) = parse_arg(arg) else {
+            return Err(format!("Invalid argument `{arg}`, use key=value"));
+        };
+        args.insert(key, value);
+    }
+
+    Ok(args.into())
+}
+
+/// Sends one command and prints the response. Exits with 0 if the command succeeded, 1 if it
+/// failed or the arguments are invalid and 2 if Conductor could not be reached.
+pub async fn run(call: CallArgs, settings_path: &str) -> i32 {
+    let args = match build_args(call.json.as_deref(), &call.args) {
+        Ok(args) => args,
+        Err(e) => {
+            log::error!("{e}");
+            return 1;
+        }
+    };
+
+    let (url, secret) = call.connection.resolve(settings_path);
+
+    let timeout = Duration::from_secs(call.timeout);
+    let options = ClientOptions {
+        secret,
+        signing_key: None,
+        timeout,
+        reconnect: false,
+    };
+
+    // The client's timeout only covers the response, this one includes connecting
+    let response = tokio::time::timeout(timeout, async {
+        let client = Client::connect(&url, options).await?;
+        client.call_raw(&call.command, args).await
+    })
+    .await;
+
+    let mut response = match response {
+        Ok(Ok(res)) => res,
+        Ok(Err(e)) => {
+            log::error!("{e}");
+            return 2;
+        }
+        Err(_) => {
+            log::error!("No response after {} seconds", call.timeout);
+            return 2;
+        }
+    };
+
+    if let Some(map) = response.as_object_mut() {
+        map.remove("messageId");
+    }
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&response).expect("failed to serialize response")
+    );
+
+    if response.get("success").and_then(|s| s.as_bool()) == Some(true) {
+        0
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pairs(pairs: &[&str]) -> Vec<String> {
+        pairs.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_values_as_json() {
+        assert_eq!(
+            parse_arg("appId=730"),
+            Some(("appId".to_owned(), json!(730)))
+        );
+        assert_eq!(
+            parse_arg("filter=[1, 2]"),
+            Some(("filter".to_owned(), json!([1, 2])))
+        );
+        assert_eq!(parse_arg("on=true"), Some(("on".to_owned(), json!(true))));
+        assert_eq!(
+            parse_arg("name=Portal 2"),
+            Some(("name".to_owned(), json!("Portal 2")))
+        );
+        // Only the first `=` splits, the value can hold more
+        assert_eq!(
+            parse_arg("launch=-novid=1"),
+            Some(("launch".to_owned(), json!("-novid=1")))
+        );
+        assert_eq!(parse_arg("empty="), Some(("empty".to_owned(), json!(""))));
+        assert_eq!(parse_arg("appId"), None);
+    }
+
+    #[test]
+    fn merges_json_and_pairs() {
+        assert_eq!(build_args(None, &[]), Ok(json!({})));
+        assert_eq!(
+            build_args(None, &pairs(&["appId=1", "appId=730"])),
+            Ok(json!({ "appId": 730 }))
+        );
+        assert_eq!(
+            build_args(
+                Some(r#"{"appId": 1, "installedOnly": true}"#),
+                &pairs(&["appId=730", "name=Portal"])
+            ),
+            Ok(json!({ "appId": 730, "installedOnly": true, "name": "Portal" }))
+        );
+
+        assert!(build_args(Some("[1]"), &[]).is_err());
+        assert!(build_args(Some("{"), &[]).is_err());
+        assert!(build_args(None, &pairs(&["appId"])).is_err());
+    }
+}
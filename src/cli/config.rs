@@ -0,0 +1,134 @@
+/*
+ *  cli/config.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::config::{self, Config, ConfigOverrides, LoadError};
+use crate::settings_file;
+use crate::tokens::HashedToken;
+use clap::Subcommand;
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Write a commented settings file with the default values
+    Init {
+        /// Replace the file if it already exists
+        #[arg(long)]
+        force: bool,
+        /// Turn on auth with this many random tokens, printed once
+        #[arg(long, value_name = "COUNT")]
+        tokens: Option<usize>,
+    },
+    /// Check the settings file and list every problem in it
+    Check,
+    /// Print the settings Conductor would run with, after environment variables and flags.
+    /// Tokens are hidden
+    Print {
+        #[command(flatten)]
+        overrides: ConfigOverrides,
+    },
+}
+
+fn init(settings_path: &str, force: bool, tokens: Option<usize>) -> i32 {
+    let (hashed, tokens): (Vec<_>, Vec<_>) = (1..=tokens.unwrap_or(0))
+        .map(|i| HashedToken::generate(format!("token-{i}"), None, None, None))
+        .unzip();
+
+    let file = match config::default_file(&hashed) {
+        Ok(file) => file,
+        Err(e) => {
+            log::error!("Failed to serialize tokens: {e}");
+            return 1;
+        }
+    };
+
+    match settings_file::write(settings_path, &file, force) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            log::error!("{settings_path} already exists, pass --force to replace it");
+            return 1;
+        }
+        Err(e) => {
+            log::error!("Failed to write {settings_path}: {e}");
+            return 1;
+        }
+    }
+
+    log::info!("Wrote {settings_path}");
+    if !tokens.is_empty() {
+        log::info!("Auth is on, clients can use these tokens. They're only stored as hashes:");
+        for token in &tokens {
+            println!("{token}");
+        }
+    }
+    0
+}
+
+fn check(settings_path: &str) -> i32 {
+    if !std::path::Path::new(settings_path).exists() {
+        log::warn!("{settings_path} does not exist, checking the defaults");
+    }
+
+    let cfg = match Config::load(settings_path) {
+        Ok(cfg) => cfg,
+        // The TOML error points at the line and column with a snippet of the file
+        Err(LoadError::Deserialize(e)) => {
+            log::error!(
+                "{settings_path} is not valid:\n{}",
+                e.to_string().trim_end()
+            );
+            return 1;
+        }
+        Err(e) => {
+            log::error!("Failed to load {settings_path}: {e}");
+            return 1;
+        }
+    };
+
+    let errors = cfg.validate();
+    if errors.is_empty() {
+        log::info!("{settings_path} is valid");
+        return 0;
+    }
+
+    for e in &errors {
+        log::error!("{e}");
+    }
+    log::error!("Found {} problem(s) in {settings_path}", errors.len());
+    1
+}
+
+fn print(settings_path: &str, overrides: &ConfigOverrides) -> i32 {
+    let mut cfg = match Config::load(settings_path) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            log::error!("Failed to load {settings_path}: {e}");
+            return 1;
+        }
+    };
+    cfg.apply(overrides);
+    cfg.redact_secrets();
+
+    match toml::to_string_pretty(&cfg) {
+        Ok(text) => {
+            print!("{text}");
+            0
+        }
+        Err(e) => {
+            log::error!("Failed to serialize settings: {e}");
+            1
+        }
+    }
+}
+
+pub fn run(cmd: ConfigCommand, settings_path: &str) -> i32 {
+    match cmd {
+        ConfigCommand::Init { force, tokens } => init(settings_path, force, tokens),
+        ConfigCommand::Check => check(settings_path),
+        ConfigCommand::Print { overrides } => print(settings_path, &overrides),
+    }
+}
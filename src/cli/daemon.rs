@@ -0,0 +1,99 @@
+/*
+ *  cli/daemon.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::config::ConfigOverrides;
+use crate::daemon::{self, DaemonError};
+use clap::Args;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How long `stop` waits for the server to shut down
+const STOP_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Args)]
+pub struct StartArgs {
+    /// Run in the background, use `conductor stop` to shut it down
+    #[arg(long)]
+    pub daemon: bool,
+    /// Where the background server writes its log (default: `conductor.log` next to the pidfile)
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+/// Starts the server in the background.
+pub async fn start(args: StartArgs, settings_path: &str) -> i32 {
+    let log = args.log_file.unwrap_or_else(daemon::log_path);
+
+    match daemon::spawn(settings_path, &log, &args.overrides.to_args()).await {
+        Ok(pid) => {
+            log::info!(
+                "Conductor started with pid {pid}, logging to {}",
+                log.display()
+            );
+            0
+        }
+        Err(e) => {
+            log::error!("{e}");
+            1
+        }
+    }
+}
+
+pub async fn stop() -> i32 {
+    match daemon::stop(STOP_TIMEOUT).await {
+        Ok(()) => {
+            log::info!("Conductor stopped");
+            0
+        }
+        Err(DaemonError::NotRunning) => {
+            log::info!("Conductor is not running");
+            0
+        }
+        Err(e) => {
+            log::error!("{e}");
+            1
+        }
+    }
+}
+
+/// Prints the server status. Exits with 3 if it's not running, like init scripts do.
+pub async fn status() -> i32 {
+    match daemon::status().await {
+        Ok(status) => {
+            println!(
+                "Conductor is running with pid {} on {}, up for {}s",
+                status["pid"],
+                status["hostname"].as_str().unwrap_or_default(),
+                status["uptime"]
+            );
+            0
+        }
+        Err(DaemonError::NotRunning) => {
+            println!("Conductor is not running");
+            3
+        }
+        Err(e) => {
+            log::error!("{e}");
+            1
+        }
+    }
+}
+
+/// Stops the running server if there is one and starts a new one in the background.
+pub async fn restart(args: StartArgs, settings_path: &str) -> i32 {
+    match daemon::stop(STOP_TIMEOUT).await {
+        Ok(()) | Err(DaemonError::NotRunning) => start(args, settings_path).await,
+        Err(e) => {
+            log::error!("{e}");
+            1
+        }
+    }
+}
@@ -0,0 +1,279 @@
+/*
+ *  cli/mod.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::api_spec::SpecFormat;
+use crate::config::Config;
+use crate::enable_cef::{DisableOutcome, disable_cef_debugging};
+use crate::steam::{Steam, SteamError};
+use crate::systemd;
+use clap::{Args, Subcommand};
+use std::path::Path;
+
+pub mod call;
+pub mod config;
+pub mod daemon;
+pub mod repl;
+pub mod shortcuts;
+pub mod token;
+pub mod tui;
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Start the server, same as running without a command. Add `--daemon` to run it in the background
+    Start(daemon::StartArgs),
+    /// Stop the server running in the background
+    Stop,
+    /// Show whether the server is running
+    Status,
+    /// Stop the server if it's running and start it again in the background
+    Restart(daemon::StartArgs),
+    /// Send a command to a running Conductor server and print the response
+    Call(call::CallArgs),
+    /// Interactive shell for a running Conductor server with completion, history and live events
+    Repl(repl::ReplArgs),
+    /// Terminal dashboard with the library, running apps and install folders of a running server
+    Tui(tui::TuiArgs),
+    /// Manage non-Steam shortcuts directly in `shortcuts.vdf`, works while Steam is closed
+    #[command(subcommand)]
+    Shortcuts(shortcuts::ShortcutsCommand),
+    /// List Steam accounts that logged in on this device
+    Users,
+    /// Turn off CEF remote debugging. Steam closes the debugger port after a restart
+    DisableCef {
+        /// Also remove the marker if it wasn't created by Conductor
+        #[arg(long)]
+        force: bool,
+    },
+    /// Write a systemd user service that starts Conductor with your session (Linux only)
+    InstallService {
+        /// Replace the unit if it already exists
+        #[arg(long)]
+        force: bool,
+        /// Also enable and start the service with `systemctl --user`
+        #[arg(long)]
+        enable: bool,
+    },
+    /// Add, list or revoke client tokens in the settings file
+    #[command(subcommand)]
+    Token(token::TokenCommand),
+    /// Create, check or print the settings file
+    #[command(subcommand)]
+    Config(config::ConfigCommand),
+    /// Print the API description the server publishes under `/.well-known/conductor/`
+    ApiSpec {
+        /// Which document to print
+        #[arg(value_enum, default_value_t = SpecFormat::Asyncapi)]
+        format: SpecFormat,
+    },
+}
+
+/// Options for commands that connect to a running server
+#[derive(Args)]
+pub struct ConnectionArgs {
+    /// Server address like `ws://localhost:7355` (default: `hostname` from the settings)
+    #[arg(long)]
+    url: Option<String>,
+    /// Secret sent with every request (default: `CONDUCTOR_TOKEN` or the first token in the settings)
+    #[arg(long)]
+    token: Option<String>,
+}
+
+impl ConnectionArgs {
+    /// Returns the server URL and secret. Flags win over `CONDUCTOR_TOKEN`, which wins over the settings file.
+    pub fn resolve(&self, settings_path: &str) -> (String, Option<String>) {
+        let cfg = Config::load(settings_path).ok();
+
+        let url = self.url.clone().unwrap_or_else(|| {
+            let hostname = cfg
+                .as_ref()
+                .map(|cfg| cfg.conductor.hostname.as_str())
+                .unwrap_or("localhost:7355");
+            format!("ws://{hostname}")
+        });
+
+        let token = self
+            .token
+            .clone()
+            .or_else(|| std::env::var("CONDUCTOR_TOKEN").ok())
+            .or_else(|| {
+                cfg.and_then(|cfg| cfg.auth)
+                    .filter(|auth| auth.enabled)
+                    .and_then(|auth| auth.tokens)
+                    .and_then(|tokens| tokens.into_iter().next())
+            });
+
+        (url, token)
+    }
+}
+
+/// Finds Steam using `steam_path` from the settings file. The settings are optional here so
+/// offline commands keep working without a config.
+fn locate_steam(settings_path: &str) -> Result<Steam, SteamError> {
+    let steam_path = Config::load(settings_path)
+        .ok()
+        .and_then(|cfg| cfg.conductor.steam_path);
+    Steam::locate(steam_path.as_deref().map(Path::new))
+}
+
+fn list_users(settings_path: &str) -> i32 {
+    let users = match locate_steam(settings_path).and_then(|steam| steam.users()) {
+        Ok(users) => users,
+        Err(e) => {
+            log::error!("{e}");
+            return 1;
+        }
+    };
+
+    if users.is_empty() {
+        println!("No users found");
+    }
+
+    for user in users {
+        println!(
+            "{}\t{}\t{}\t{}{}",
+            user.account_id,
+            user.steam_id,
+            user.account_name,
+            user.persona_name,
+            if user.most_recent {
+                "\t(most recent)"
+            } else {
+                ""
+            }
+        );
+    }
+
+    0
+}
+
+fn disable_cef(settings_path: &str, force: bool) -> i32 {
+    let steam = match locate_steam(settings_path) {
+        Ok(steam) => steam,
+        Err(e) => {
+            log::error!("{e}");
+            return 1;
+        }
+    };
+
+    match disable_cef_debugging(&steam, force) {
+        Ok(DisableOutcome::Removed) => {
+            log::info!("CEF remote debugging disabled, restart Steam to apply");
+            0
+        }
+        Ok(DisableOutcome::NotEnabled) => {
+            log::info!("CEF remote debugging is not enabled");
+            0
+        }
+        Ok(DisableOutcome::KeptUserFile) => {
+            log::warn!(
+                "{} was not created by Conductor, pass --force to remove it anyway",
+                steam.cef_debugging_marker().display()
+            );
+            1
+        }
+        Err(e) => {
+            log::error!("Failed to disable CEF remote debugging: {e}");
+            1
+        }
+    }
+}
+
+fn install_service(settings_path: &str, force: bool, enable: bool) -> i32 {
+    if !cfg!(target_os = "linux") {
+        log::error!("Services can only be installed on Linux");
+        return 1;
+    }
+
+    let settings_path = match std::fs::canonicalize(settings_path) {
+        Ok(path) => path,
+        Err(e) => {
+            log::error!("Could not find settings file {settings_path}: {e}");
+            return 1;
+        }
+    };
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => {
+            log::error!("Could not get current executable: {e}");
+            return 1;
+        }
+    };
+    let Some(dir) = systemd::user_unit_dir() else {
+        log::error!("Could not find the systemd user folder, set HOME or XDG_CONFIG_HOME");
+        return 1;
+    };
+
+    let path = dir.join(systemd::UNIT_NAME);
+    if path.exists() && !force {
+        log::error!(
+            "{} already exists, pass --force to replace it",
+            path.display()
+        );
+        return 1;
+    }
+
+    let written = std::fs::create_dir_all(&dir)
+        .and_then(|_| std::fs::write(&path, systemd::unit_file(&exe, &settings_path)));
+    if let Err(e) = written {
+        log::error!("Failed to write {}: {e}", path.display());
+        return 1;
+    }
+    log::info!("Wrote {}", path.display());
+
+    if !enable {
+        log::info!(
+            "Run `systemctl --user enable --now {}` to start it",
+            systemd::UNIT_NAME
+        );
+        return 0;
+    }
+
+    for args in [
+        &["--user", "daemon-reload"][..],
+        &["--user", "enable", "--now", systemd::UNIT_NAME],
+    ] {
+        match std::process::Command::new("systemctl").args(args).status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => {
+                log::error!("`systemctl {}` failed with {status}", args.join(" "));
+                return 1;
+            }
+            Err(e) => {
+                log::error!("Failed to run systemctl: {e}");
+                return 1;
+            }
+        }
+    }
+
+    log::info!("Conductor service enabled and started");
+    0
+}
+
+/// Runs a subcommand and returns the process exit code.
+pub async fn run(command: Command, settings_path: &str) -> i32 {
+    match command {
+        Command::Start(args) => daemon::start(args, settings_path).await,
+        Command::Stop => daemon::stop().await,
+        Command::Status => daemon::status().await,
+        Command::Restart(args) => daemon::restart(args, settings_path).await,
+        Command::Call(args) => call::run(args, settings_path).await,
+        Command::Repl(args) => repl::run(args, settings_path).await,
+        Command::Tui(args) => tui::run(args, settings_path).await,
+        Command::Shortcuts(cmd) => shortcuts::run(cmd, settings_path),
+        Command::Users => list_users(settings_path),
+        Command::DisableCef { force } => disable_cef(settings_path, force),
+        Command::InstallService { force, enable } => install_service(settings_path, force, enable),
+        Command::Token(cmd) => token::run(cmd, settings_path),
+        Command::Config(cmd) => config::run(cmd, settings_path),
+        Command::ApiSpec { format } => {
+            println!("{}", format.generate());
+            0
+        }
+    }
+}
@@ -0,0 +1,345 @@
+/*
+ *  cli/repl.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::cli::ConnectionArgs;
+use crate::cli::call::parse_arg;
+use crate::steam;
+use clap::Args;
+use conductor_client::{Client, ClientOptions, Error};
+use conductor_schema::schema;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::{Context, ExternalPrinter, Helper, Highlighter, Hinter, Validator};
+use std::path::PathBuf;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+
+/// Command names with their argument names, in schema order
+fn commands() -> impl Iterator<Item = (&'static str, Vec<&'static str>)> {
+    schema().commands.iter().map(|c| {
+        (
+            c.name.as_str(),
+            c.args.iter().map(|f| f.name.as_str()).collect(),
+        )
+    })
+}
+
+const BUILTINS: [&str; 3] = ["help", "exit", "quit"];
+
+/// How long to wait for a response before giving the prompt back
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Args)]
+pub struct ReplArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+}
+
+#[derive(Helper, Hinter, Highlighter, Validator)]
+struct ReplHelper;
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos].rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &line[start..pos];
+
+        if start == 0 {
+            let candidates = commands()
+                .map(|(name, _)| name)
+                .chain(BUILTINS)
+                .filter(|name| name.starts_with(word))
+                .map(str::to_owned)
+                .collect();
+            return Ok((start, candidates));
+        }
+
+        let command = line.split_whitespace().next().unwrap_or_default();
+        let Some((_, keys)) = commands().find(|(name, _)| *name == command) else {
+            return Ok((start, Vec::new()));
+        };
+
+        let candidates = keys
+            .iter()
+            .filter(|key| key.starts_with(word))
+            .filter(|key| !line.contains(&format!(" {key}=")))
+            .map(|key| format!("{key}="))
+            .collect();
+
+        Ok((start, candidates))
+    }
+}
+
+/// Splits a line on whitespace, keeping quoted strings and JSON arrays or objects in one piece.
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in line.chars() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else {
+            match c {
+                '"' => in_string = true,
+                '[' | '{' => depth += 1,
+                ']' | '}' => depth -= 1,
+                c if c.is_whitespace() && depth == 0 => {
+                    if !word.is_empty() {
+                        words.push(std::mem::take(&mut word));
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        word.push(c);
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+/// Parses `Command key=value ...` or `Command {"key": value}` into a command and its args.
+fn parse_line(line: &str) -> Result<(String, serde_json::Value), String> {
+    let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+
+    if rest.starts_with('{') {
+        return match serde_json::from_str::<serde_json::Value>(rest) {
+            Ok(args @ serde_json::Value::Object(_)) => Ok((command.to_owned(), args)),
+            _ => Err("Arguments are not a valid JSON object".to_owned()),
+        };
+    }
+
+    let mut args = serde_json::Map::new();
+    for word in split_words(rest) {
+        let Some((key, value)) = parse_arg(&word) else {
+            return Err(format!("Invalid argument `{word}`, use key=value"));
+        };
+        args.insert(key, value);
+    }
+
+    Ok((command.to_owned(), args.into()))
+}
+
+fn print_help() {
+    println!("Usage: <Command> key=value ... or <Command> {{\"key\": value}}");
+    println!("Press Tab to complete commands and argument names, `exit` or Ctrl+D to quit.\n");
+    println!("Commands:");
+    for (name, keys) in commands() {
+        println!("  {name} {}", keys.join(" "));
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    steam::home_dir().map(|home| home.join(".conductor_history"))
+}
+
+/// Runs the line editor on its own thread since it blocks. Each line is sent to `line_tx` and the
+/// prompt comes back once something is sent to the returned channel. Dropping the channel stops
+/// the editor, the thread is done once it saved the history. A printer for writing above the
+/// prompt is sent to `printer_tx` if the terminal supports one.
+fn spawn_editor(
+    line_tx: UnboundedSender<String>,
+    printer_tx: std::sync::mpsc::Sender<Box<dyn ExternalPrinter + Send>>,
+) -> (std::sync::mpsc::Sender<()>, JoinHandle<()>) {
+    let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+
+    let handle = std::thread::spawn(move || {
+        let mut rl = match rustyline::Editor::new() {
+            Ok(rl) => rl,
+            Err(e) => {
+                log::error!("Failed to open terminal: {e}");
+                return;
+            }
+        };
+        rl.set_helper(Some(ReplHelper));
+
+        if let Ok(printer) = rl.create_external_printer() {
+            _ = printer_tx.send(Box::new(printer));
+        }
+        drop(printer_tx);
+
+        let history = history_path();
+        if let Some(path) = &history {
+            _ = rl.load_history(path);
+        }
+
+        loop {
+            match rl.readline("conductor> ") {
+                Ok(line) => {
+                    let line = line.trim().to_owned();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    _ = rl.add_history_entry(&line);
+                    if line_tx.send(line).is_err() || done_rx.recv().is_err() {
+                        break;
+                    }
+                }
+                // Ctrl+C clears the line like in a shell
+                Err(ReadlineError::Interrupted) => continue,
+                Err(_) => break,
+            }
+        }
+
+        if let Some(path) = &history {
+            _ = rl.save_history(path);
+        }
+    });
+
+    (done_tx, handle)
+}
+
+pub async fn run(args: ReplArgs, settings_path: &str) -> i32 {
+    let (url, secret) = args.connection.resolve(settings_path);
+    let options = ClientOptions {
+        secret,
+        signing_key: None,
+        timeout: RESPONSE_TIMEOUT,
+        reconnect: true,
+    };
+
+    let client = match Client::connect(&url, options).await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("{e}");
+            return 2;
+        }
+    };
+    println!("Connected to {url}. Type `help` for a list of commands.");
+
+    let (line_tx, mut line_rx) = unbounded_channel::<String>();
+    let (printer_tx, printer_rx) = std::sync::mpsc::channel();
+    let (done_tx, editor) = spawn_editor(line_tx, printer_tx);
+    let mut printer = tokio::task::spawn_blocking(move || printer_rx.recv().ok())
+        .await
+        .ok()
+        .flatten();
+
+    // Print events above the prompt while the user is typing
+    let mut events = client.events();
+    tokio::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            let text = format!(
+                "[event] {}",
+                serde_json::to_string_pretty(&event).expect("failed to serialize event")
+            );
+            match printer.as_mut() {
+                Some(printer) => _ = printer.print(text),
+                None => println!("{text}"),
+            }
+        }
+    });
+
+    while let Some(line) = line_rx.recv().await {
+        match line.as_str() {
+            "exit" | "quit" => break,
+            "help" => print_help(),
+            _ => match parse_line(&line) {
+                Ok((command, args)) => match client.call_raw(&command, args).await {
+                    Ok(mut res) => {
+                        if let Some(map) = res.as_object_mut() {
+                            map.remove("messageId");
+                        }
+                        println!(
+                            "{}",
+                            serde_json::to_string_pretty(&res)
+                                .expect("failed to serialize response")
+                        );
+                    }
+                    Err(Error::Disconnected) => println!("Lost connection, reconnecting..."),
+                    Err(e) => println!("{e}"),
+                },
+                Err(e) => println!("{e}"),
+            },
+        }
+        _ = done_tx.send(());
+    }
+
+    // Let the editor save the history before the process exits
+    drop(done_tx);
+    _ = tokio::task::spawn_blocking(move || editor.join()).await;
+
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn splits_words() {
+        assert_eq!(split_words("  a=1   b=2 "), ["a=1", "b=2"]);
+        assert_eq!(
+            split_words(r#"name="Portal 2" path="C:\\Games\" x""#),
+            [r#"name="Portal 2""#, r#"path="C:\\Games\" x""#]
+        );
+        assert_eq!(
+            split_words(r#"filter=[1, 2] opts={"a": [3, 4], "b": "}"}"#),
+            ["filter=[1, 2]", r#"opts={"a": [3, 4], "b": "}"}"#]
+        );
+        assert!(split_words("").is_empty());
+    }
+
+    #[test]
+    fn parses_lines() {
+        assert_eq!(parse_line("GetApps"), Ok(("GetApps".to_owned(), json!({}))));
+        assert_eq!(
+            parse_line(r#"RunApp appId=730 name="Counter Strike" ids=[1, 2]"#),
+            Ok((
+                "RunApp".to_owned(),
+                json!({ "appId": 730, "name": "Counter Strike", "ids": [1, 2] })
+            ))
+        );
+        assert_eq!(
+            parse_line("RunApp name=Portal appId=1 appId=730"),
+            Ok((
+                "RunApp".to_owned(),
+                json!({ "name": "Portal", "appId": 730 })
+            ))
+        );
+        assert_eq!(
+            parse_line(r#"GetAppInfo  {"appId": 730, "extra": {"a": 1}}"#),
+            Ok((
+                "GetAppInfo".to_owned(),
+                json!({ "appId": 730, "extra": { "a": 1 } })
+            ))
+        );
+
+        assert!(parse_line(r#"GetAppInfo {"appId": 730"#).is_err());
+        assert!(parse_line("RunApp appId").is_err());
+    }
+}
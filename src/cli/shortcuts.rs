@@ -0,0 +1,220 @@
+/*
+ *  cli/shortcuts.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::cli::locate_steam;
+use crate::shortcuts::{Shortcut, Shortcuts, quote_path};
+use crate::steam::{self, SteamError};
+use clap::{Args, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct Target {
+    /// Path to the `shortcuts.vdf` file to edit (default: the file of the selected user)
+    #[arg(long, conflicts_with = "user")]
+    file: Option<PathBuf>,
+    /// Account ID of the Steam user to edit (default: the user that logged in last)
+    #[arg(long)]
+    user: Option<u32>,
+}
+
+impl Target {
+    fn path(&self, settings_path: &str) -> Result<PathBuf, SteamError> {
+        if let Some(file) = &self.file {
+            return Ok(file.clone());
+        }
+
+        let steam = locate_steam(settings_path)?;
+        let user = steam.user(self.user)?;
+        Ok(steam.shortcuts_path(user.account_id))
+    }
+}
+
+#[derive(Args)]
+pub struct ShortcutFields {
+    /// Game title
+    #[arg(long)]
+    name: Option<String>,
+    /// Folder the game starts in
+    #[arg(long)]
+    start_dir: Option<String>,
+    /// Path to the game icon
+    #[arg(long)]
+    icon: Option<String>,
+    /// Launch options passed to the game
+    #[arg(long, allow_hyphen_values = true)]
+    launch_options: Option<String>,
+    /// Library tag, can be repeated. Replaces all existing tags
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+    /// Hide the shortcut in the library
+    #[arg(long)]
+    hidden: Option<bool>,
+}
+
+#[derive(Subcommand)]
+pub enum ShortcutsCommand {
+    /// List all shortcuts
+    List {
+        #[command(flatten)]
+        target: Target,
+        /// Print shortcuts as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Add a new shortcut and print its app ID
+    Add {
+        #[command(flatten)]
+        target: Target,
+        /// Path to the game executable
+        #[arg(long)]
+        exe: String,
+        #[command(flatten)]
+        fields: ShortcutFields,
+        /// Save even if Steam is running, it will overwrite the changes when it exits
+        #[arg(long)]
+        force: bool,
+    },
+    /// Change an existing shortcut. The app ID stays the same so artwork keeps working
+    Edit {
+        #[command(flatten)]
+        target: Target,
+        /// App ID of the shortcut
+        app_id: u32,
+        /// Path to the game executable
+        #[arg(long)]
+        exe: Option<String>,
+        #[command(flatten)]
+        fields: ShortcutFields,
+        /// Save even if Steam is running, it will overwrite the changes when it exits
+        #[arg(long)]
+        force: bool,
+    },
+    /// Remove a shortcut
+    Remove {
+        #[command(flatten)]
+        target: Target,
+        /// App ID of the shortcut
+        app_id: u32,
+        /// Save even if Steam is running, it will overwrite the changes when it exits
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+fn apply_fields(shortcut: &mut Shortcut, fields: ShortcutFields) {
+    if let Some(name) = fields.name {
+        shortcut.name = name;
+    }
+    if let Some(start_dir) = fields.start_dir {
+        shortcut.start_dir = quote_path(&start_dir);
+    }
+    if let Some(icon) = fields.icon {
+        shortcut.icon = icon;
+    }
+    if let Some(launch_options) = fields.launch_options {
+        shortcut.launch_options = launch_options;
+    }
+    if !fields.tags.is_empty() {
+        shortcut.tags = fields.tags;
+    }
+    if let Some(hidden) = fields.hidden {
+        shortcut.is_hidden = hidden;
+    }
+}
+
+pub fn run(cmd: ShortcutsCommand, settings_path: &str) -> i32 {
+    let (target, force) = match &cmd {
+        ShortcutsCommand::List { target, .. } => (target, true),
+        ShortcutsCommand::Add { target, force, .. }
+        | ShortcutsCommand::Edit { target, force, .. }
+        | ShortcutsCommand::Remove { target, force, .. } => (target, *force),
+    };
+
+    // Steam keeps its own copy and writes it back when it exits
+    if !force && steam::is_running() {
+        log::error!("Steam is running and would undo the changes, close it first or pass --force");
+        return 1;
+    }
+
+    let path = match target.path(settings_path) {
+        Ok(path) => path,
+        Err(e) => {
+            log::error!("{e}");
+            return 1;
+        }
+    };
+
+    let mut shortcuts = match Shortcuts::load(&path) {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("Failed to load shortcuts from {}: {}", path.display(), e);
+            return 1;
+        }
+    };
+
+    match cmd {
+        ShortcutsCommand::List { json, .. } => {
+            if json {
+                let list = shortcuts.iter().collect::<Vec<_>>();
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&list).expect("failed to serialize shortcuts")
+                );
+            } else if shortcuts.is_empty() {
+                println!("No shortcuts found");
+            } else {
+                for shortcut in shortcuts.iter() {
+                    println!("{}\t{}\t{}", shortcut.app_id, shortcut.name, shortcut.exe);
+                }
+            }
+            return 0;
+        }
+        ShortcutsCommand::Add { exe, fields, .. } => {
+            let mut shortcut = Shortcut::new(&exe, fields.name.as_deref(), None);
+            apply_fields(&mut shortcut, fields);
+
+            match shortcuts.add(shortcut) {
+                Ok(app_id) => println!("{app_id}"),
+                Err(e) => {
+                    log::error!("{e}");
+                    return 1;
+                }
+            }
+        }
+        ShortcutsCommand::Edit {
+            app_id,
+            exe,
+            fields,
+            ..
+        } => {
+            let Some(shortcut) = shortcuts.get_mut(app_id) else {
+                log::error!("No shortcut with app ID {app_id}");
+                return 1;
+            };
+
+            if let Some(exe) = exe {
+                shortcut.exe = quote_path(&exe);
+            }
+            apply_fields(shortcut, fields);
+        }
+        ShortcutsCommand::Remove { app_id, .. } => {
+            if let Err(e) = shortcuts.remove(app_id) {
+                log::error!("{e}");
+                return 1;
+            }
+        }
+    }
+
+    if let Err(e) = shortcuts.save(&path) {
+        log::error!("Failed to save shortcuts to {}: {}", path.display(), e);
+        return 1;
+    }
+
+    0
+}
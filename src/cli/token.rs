@@ -0,0 +1,205 @@
+/*
+ *  cli/token.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::config::Config;
+use crate::settings_file::{auth_array, has_entry, read_document, to_table, write_document};
+use crate::tokens::{self, HashedToken, SharedKey};
+use chrono::{DateTime, SubsecRound, Utc};
+use clap::Subcommand;
+
+#[derive(Subcommand)]
+pub enum TokenCommand {
+    /// Generate a new token and print it. Only a hash is stored, so this is the only time it's shown
+    Add {
+        /// Name to tell the token apart, like the device or person using it
+        name: String,
+        /// When the token starts working, a date like `2025-07-01T00:00:00Z` or a time from now
+        /// like `12h` or `7d`
+        #[arg(long, value_name = "WHEN", value_parser = parse_time)]
+        not_before: Option<DateTime<Utc>>,
+        /// When the token stops working, in the same format as `--not-before`
+        #[arg(long, value_name = "WHEN", value_parser = parse_time)]
+        expires: Option<DateTime<Utc>>,
+        /// Command the token can run, can be repeated. Tokens can run every command by default
+        #[arg(long = "scope", value_name = "COMMAND")]
+        scopes: Vec<String>,
+        /// Make a key for signed requests instead. It's stored as-is since the server needs it to
+        /// check signatures
+        #[arg(long)]
+        signing: bool,
+    },
+    /// List the tokens and signing keys in the settings file
+    List,
+    /// Remove a token or signing key, running servers stop accepting it when they reload the
+    /// settings
+    Revoke {
+        /// Name of the token or key
+        name: String,
+    },
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    tokens::parse_time(value, Utc::now().trunc_subsecs(0))
+}
+
+fn add(
+    settings_path: &str,
+    name: String,
+    not_before: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+    scopes: Vec<String>,
+    signing: bool,
+) -> Result<(), String> {
+    if let (Some(not_before), Some(expires_at)) = (not_before, expires_at)
+        && expires_at <= not_before
+    {
+        return Err("The token would expire before it starts working".to_owned());
+    }
+
+    let schema = conductor_schema::schema();
+    if let Some(scope) = scopes.iter().find(|scope| schema.command(scope).is_none()) {
+        return Err(format!("Unknown command {scope}"));
+    }
+
+    let mut doc = read_document(settings_path)?;
+    if has_entry(auth_array(&mut doc, "hashed_tokens")?, &name)
+        || has_entry(auth_array(&mut doc, "signing_keys")?, &name)
+    {
+        return Err(format!("A token named {name:?} already exists"));
+    }
+
+    let scopes = (!scopes.is_empty()).then_some(scopes);
+    let secret = if signing {
+        let entry = SharedKey::generate(name.clone(), not_before, expires_at, scopes);
+        auth_array(&mut doc, "signing_keys")?.push(to_table(&entry)?);
+        entry.key
+    } else {
+        let (entry, token) = HashedToken::generate(name.clone(), not_before, expires_at, scopes);
+        auth_array(&mut doc, "hashed_tokens")?.push(to_table(&entry)?);
+        token
+    };
+
+    write_document(settings_path, &doc)?;
+
+    if signing {
+        log::info!("Added signing key {name:?}, give it to the client along with the name:");
+    } else {
+        log::info!("Added token {name:?}, copy it now since it's only stored as a hash:");
+    }
+    println!("{secret}");
+
+    let auth_enabled = Config::load(settings_path)
+        .ok()
+        .and_then(|cfg| cfg.auth)
+        .is_some_and(|auth| auth.enabled);
+    if !auth_enabled {
+        log::warn!("Auth is off, set `enabled = true` under [auth] so clients need a token");
+    }
+    Ok(())
+}
+
+fn list(settings_path: &str) -> Result<(), String> {
+    let cfg =
+        Config::load(settings_path).map_err(|e| format!("Failed to load {settings_path}: {e}"))?;
+    let auth = cfg.auth.unwrap_or_default();
+    let now = Utc::now();
+
+    if auth.hashed_tokens.is_empty() && auth.signing_keys.is_empty() {
+        println!("No tokens found");
+    }
+
+    let hashed = auth.hashed_tokens.iter().map(|token| {
+        let HashedToken {
+            name,
+            created_at,
+            not_before,
+            expires_at,
+            scopes,
+            ..
+        } = token;
+        ("token", name, created_at, not_before, expires_at, scopes)
+    });
+    let signing = auth.signing_keys.iter().map(|key| {
+        let SharedKey {
+            name,
+            created_at,
+            not_before,
+            expires_at,
+            scopes,
+            ..
+        } = key;
+        ("signing", name, created_at, not_before, expires_at, scopes)
+    });
+
+    for (kind, name, created_at, not_before, expires_at, scopes) in hashed.chain(signing) {
+        let starts = match not_before {
+            Some(not_before) if now < *not_before => format!("\tstarts {not_before}"),
+            _ => String::new(),
+        };
+        let expires = match expires_at {
+            Some(expires_at) if *expires_at <= now => format!("expired {expires_at}"),
+            Some(expires_at) => format!("expires {expires_at}"),
+            None => "never expires".to_owned(),
+        };
+        let scopes = scopes
+            .as_ref()
+            .map(|scopes| scopes.join(","))
+            .unwrap_or_else(|| "all commands".to_owned());
+
+        println!("{name}\t{kind}\tcreated {created_at}{starts}\t{expires}\t{scopes}");
+    }
+
+    let plain = auth.tokens.as_deref().unwrap_or_default().len();
+    if plain > 0 {
+        log::warn!(
+            "{plain} plain token(s) in `auth.tokens`, replace them with `conductor token add`"
+        );
+    }
+    Ok(())
+}
+
+fn revoke(settings_path: &str, name: &str) -> Result<(), String> {
+    let mut doc = read_document(settings_path)?;
+    let mut removed = false;
+    for key in ["hashed_tokens", "signing_keys"] {
+        let list = auth_array(&mut doc, key)?;
+        let before = list.len();
+        list.retain(|token| token.get("name").and_then(|n| n.as_str()) != Some(name));
+        removed |= list.len() != before;
+    }
+    if !removed {
+        return Err(format!("No token named {name:?}"));
+    }
+
+    write_document(settings_path, &doc)?;
+    log::info!("Revoked token {name:?}");
+    Ok(())
+}
+
+pub fn run(cmd: TokenCommand, settings_path: &str) -> i32 {
+    let result = match cmd {
+        TokenCommand::Add {
+            name,
+            not_before,
+            expires,
+            scopes,
+            signing,
+        } => add(settings_path, name, not_before, expires, scopes, signing),
+        TokenCommand::List => list(settings_path),
+        TokenCommand::Revoke { name } => revoke(settings_path, &name),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            log::error!("{e}");
+            1
+        }
+    }
+}
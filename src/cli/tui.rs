@@ -0,0 +1,623 @@
+/*
+ *  cli/tui.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::cli::ConnectionArgs;
+use clap::Args;
+use conductor_client::commands::{
+    AppType, Command, Empty, GetAppInfo, GetAppInfoReturns, GetApps, GetAppsReturns,
+    GetInstallFolders, GetInstallFoldersReturns, GetRunningApps, GetRunningAppsReturns, InstallApp,
+    InstallFolder, RunApp, TerminateApp, UninstallApp,
+};
+use conductor_client::{Client, ClientOptions, Error};
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use futures_util::StreamExt;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Cell, List, ListItem, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+/// App types shown in the library, everything except Steam's own client features
+const LIBRARY_TYPES: [AppType; 6] = [
+    AppType::Game,
+    AppType::Software,
+    AppType::Tool,
+    AppType::Demo,
+    AppType::Playtest,
+    AppType::Shortcut,
+];
+
+/// How often running apps are refreshed
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long to wait before trying again if the first connection fails. Later reconnects are
+/// handled by the client.
+const RECONNECT_DELAY: Duration = Duration::from_secs(3);
+
+#[derive(Args)]
+pub struct TuiArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+}
+
+struct LibraryApp {
+    id: u32,
+    info: Option<GetAppInfoReturns>,
+}
+
+impl LibraryApp {
+    fn name(&self) -> String {
+        match &self.info {
+            Some(info) => info.display_name.clone(),
+            None => format!("App {}", self.id),
+        }
+    }
+}
+
+/// Finished request, sent back to the main loop by the task that made it
+enum Response {
+    Connected(Result<Client, Error>),
+    Apps(Result<GetAppsReturns, Error>),
+    AppInfo(u32, Result<GetAppInfoReturns, Error>),
+    Folders(Result<GetInstallFoldersReturns, Error>),
+    RunningApps(Result<GetRunningAppsReturns, Error>),
+    Action(String, Result<Empty, Error>),
+}
+
+struct Dashboard {
+    url: String,
+    options: ClientOptions,
+    client: Option<Client>,
+    /// Set while the first connection is being made in the background
+    connecting: bool,
+    /// Why the first connection failed and when
+    connect_error: Option<(String, Instant)>,
+    responses: UnboundedSender<Response>,
+    apps: Vec<LibraryApp>,
+    folders: Vec<InstallFolder>,
+    running: Vec<u32>,
+    /// Apps with a `GetAppInfo` request still running
+    loading_details: HashSet<u32>,
+    /// Fetch details for every app with the next library, not only new ones
+    refresh_details: bool,
+    installed_only: bool,
+    table: TableState,
+    /// App waiting for the user to confirm an uninstall
+    confirm_uninstall: Option<u32>,
+    status: String,
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+fn type_name(app_type: AppType) -> &'static str {
+    match app_type {
+        AppType::Game => "Game",
+        AppType::Software => "Software",
+        AppType::Tool => "Tool",
+        AppType::Demo => "Demo",
+        AppType::Playtest => "Playtest",
+        AppType::Shortcut => "Shortcut",
+        _ => "Other",
+    }
+}
+
+impl Dashboard {
+    fn new(url: String, options: ClientOptions, responses: UnboundedSender<Response>) -> Self {
+        Self {
+            url,
+            options,
+            client: None,
+            connecting: false,
+            connect_error: None,
+            responses,
+            apps: Vec::new(),
+            folders: Vec::new(),
+            running: Vec::new(),
+            loading_details: HashSet::new(),
+            refresh_details: false,
+            installed_only: false,
+            table: TableState::default(),
+            confirm_uninstall: None,
+            status: String::new(),
+        }
+    }
+
+    /// Connects in the background so the dashboard keeps drawing, the result comes back as
+    /// `Response::Connected`.
+    fn connect(&mut self) {
+        self.connecting = true;
+        let url = self.url.clone();
+        let options = self.options.clone();
+        let responses = self.responses.clone();
+
+        tokio::spawn(async move {
+            let res = Client::connect(&url, options).await;
+            _ = responses.send(Response::Connected(res));
+        });
+    }
+
+    /// Runs `command` in the background and sends the response through `wrap` to the main loop.
+    fn call<C, F>(&self, command: C, wrap: F)
+    where
+        C: Command + Send + Sync + 'static,
+        C::Returns: Send,
+        F: FnOnce(Result<C::Returns, Error>) -> Response + Send + 'static,
+    {
+        let Some(client) = self.client.clone() else {
+            return;
+        };
+        let responses = self.responses.clone();
+
+        tokio::spawn(async move {
+            let res = client.call(&command).await;
+            _ = responses.send(wrap(res));
+        });
+    }
+
+    /// Fetches the library, install folders and running apps again.
+    fn reload(&mut self) {
+        self.call(
+            GetApps {
+                type_filter: Some(LIBRARY_TYPES.to_vec()),
+                installed_only: None,
+            },
+            Response::Apps,
+        );
+        self.call(GetInstallFolders::default(), Response::Folders);
+        self.call(GetRunningApps::default(), Response::RunningApps);
+    }
+
+    /// Library apps in display order, hiding uninstalled ones if the filter is on
+    fn visible_apps(&self) -> Vec<&LibraryApp> {
+        self.apps
+            .iter()
+            .filter(|app| {
+                !self.installed_only || app.info.as_ref().is_some_and(|info| info.installed)
+            })
+            .collect()
+    }
+
+    fn selected_app(&self) -> Option<(u32, String)> {
+        let apps = self.visible_apps();
+        let app = apps.get(self.table.selected()?)?;
+        Some((app.id, app.name()))
+    }
+
+    fn sort_apps(&mut self) {
+        let selected = self.selected_app().map(|(id, _)| id);
+
+        self.apps
+            .sort_by_cached_key(|app| (app.info.is_none(), app.name().to_lowercase()));
+
+        if let Some(id) = selected {
+            let index = self.visible_apps().iter().position(|app| app.id == id);
+            self.table.select(index);
+        }
+    }
+
+    fn handle_response(&mut self, res: Response) {
+        match res {
+            Response::Connected(Ok(client)) => {
+                self.connecting = false;
+                self.client = Some(client);
+                self.connect_error = None;
+                self.reload();
+            }
+            Response::Connected(Err(e)) => {
+                self.connecting = false;
+                self.connect_error = Some((e.to_string(), Instant::now()));
+            }
+            Response::Apps(Ok(res)) => {
+                let mut old: HashMap<u32, LibraryApp> =
+                    self.apps.drain(..).map(|app| (app.id, app)).collect();
+
+                self.apps = res
+                    .app_ids
+                    .iter()
+                    .map(|id| {
+                        old.remove(id).unwrap_or(LibraryApp {
+                            id: *id,
+                            info: None,
+                        })
+                    })
+                    .collect();
+                self.sort_apps();
+                if self.table.selected().is_none() && !self.apps.is_empty() {
+                    self.table.select(Some(0));
+                }
+
+                self.status = format!("Loaded {} apps", res.app_ids.len());
+
+                // Details rarely change, only ask for apps we don't have them for yet
+                let refresh = std::mem::take(&mut self.refresh_details);
+                let missing: Vec<u32> = self
+                    .apps
+                    .iter()
+                    .filter(|app| refresh || app.info.is_none())
+                    .map(|app| app.id)
+                    .filter(|id| !self.loading_details.contains(id))
+                    .collect();
+                for app_id in missing {
+                    self.loading_details.insert(app_id);
+                    self.call(GetAppInfo { app_id }, move |res| {
+                        Response::AppInfo(app_id, res)
+                    });
+                }
+            }
+            Response::AppInfo(id, res) => {
+                self.loading_details.remove(&id);
+
+                // Missing details just leave the app without a name
+                if let Ok(info) = res
+                    && let Some(app) = self.apps.iter_mut().find(|app| app.id == id)
+                {
+                    app.info = Some(info);
+                }
+
+                if self.loading_details.is_empty() {
+                    self.sort_apps();
+                }
+            }
+            Response::Folders(Ok(res)) => self.folders = res.folders,
+            Response::RunningApps(Ok(res)) => self.running = res.app_ids,
+            Response::Action(action, Ok(_)) => self.status = format!("{action}: done"),
+            Response::Action(action, Err(e)) => self.status = format!("{action} failed: {e}"),
+            Response::Apps(Err(e)) | Response::Folders(Err(e)) | Response::RunningApps(Err(e)) => {
+                self.status = format!("Request failed: {e}")
+            }
+        }
+    }
+
+    fn run_action<C>(&mut self, command: C, verb: &str)
+    where
+        C: Command<Returns = Empty> + Send + Sync + 'static,
+    {
+        let Some((_, name)) = self.selected_app() else {
+            return;
+        };
+
+        let action = format!("{verb} {name}");
+        self.status = format!("{action}...");
+        self.call(command, move |res| Response::Action(action, res));
+    }
+
+    /// Handles a key press. Returns `false` when the user wants to quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if let Some(app_id) = self.confirm_uninstall.take() {
+            if key.code == KeyCode::Char('y') {
+                self.run_action(
+                    UninstallApp {
+                        app_id,
+                        auto_confirm: Some(true),
+                    },
+                    "Uninstalling",
+                );
+            } else {
+                self.status = "Uninstall cancelled".to_owned();
+            }
+            return true;
+        }
+
+        let len = self.visible_apps().len();
+        let selected = self.table.selected().unwrap_or(0);
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Down | KeyCode::Char('j') => self
+                .table
+                .select(Some((selected + 1).min(len.saturating_sub(1)))),
+            KeyCode::Up | KeyCode::Char('k') => self.table.select(Some(selected.saturating_sub(1))),
+            KeyCode::PageDown => self
+                .table
+                .select(Some((selected + 20).min(len.saturating_sub(1)))),
+            KeyCode::PageUp => self.table.select(Some(selected.saturating_sub(20))),
+            KeyCode::Home => self.table.select(Some(0)),
+            KeyCode::End => self.table.select(Some(len.saturating_sub(1))),
+            KeyCode::Char('f') => {
+                self.installed_only = !self.installed_only;
+                self.table.select(Some(0));
+            }
+            KeyCode::F(5) | KeyCode::Char('R') => {
+                self.status = "Reloading...".to_owned();
+                self.refresh_details = true;
+                self.reload();
+            }
+            KeyCode::Enter | KeyCode::Char('r') => {
+                if let Some((app_id, _)) = self.selected_app() {
+                    self.run_action(RunApp { app_id }, "Starting");
+                }
+            }
+            KeyCode::Char('t') => {
+                if let Some((app_id, _)) = self.selected_app() {
+                    self.run_action(TerminateApp { app_id }, "Stopping");
+                }
+            }
+            KeyCode::Char('i') => {
+                if let Some((app_id, _)) = self.selected_app() {
+                    // Nobody is in front of the screen to pick a folder, use the default one
+                    let folder_idx = self
+                        .folders
+                        .iter()
+                        .find(|f| f.is_default == Some(true))
+                        .and_then(|f| f.index)
+                        .map(|index| index as u32);
+                    self.run_action(InstallApp { app_id, folder_idx }, "Installing");
+                }
+            }
+            KeyCode::Char('u') => {
+                if let Some((id, name)) = self.selected_app() {
+                    self.confirm_uninstall = Some(id);
+                    self.status = format!("Uninstall {name}? Press y to confirm");
+                }
+            }
+            _ => {}
+        }
+
+        true
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(2),
+        ])
+        .areas(frame.area());
+        let [library, side] =
+            Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)])
+                .areas(body);
+        let [running, folders] =
+            Layout::vertical([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(side);
+
+        self.draw_header(frame, header);
+        self.draw_library(frame, library);
+        self.draw_running(frame, running);
+        self.draw_folders(frame, folders);
+
+        let help = Line::from(vec![
+            "↑↓".bold(),
+            " select  ".into(),
+            "r".bold(),
+            " run  ".into(),
+            "t".bold(),
+            " terminate  ".into(),
+            "i".bold(),
+            " install  ".into(),
+            "u".bold(),
+            " uninstall  ".into(),
+            "f".bold(),
+            " installed only  ".into(),
+            "R".bold(),
+            " reload  ".into(),
+            "q".bold(),
+            " quit".into(),
+        ]);
+        frame.render_widget(
+            Paragraph::new(vec![Line::from(self.status.as_str()), help]),
+            footer,
+        );
+    }
+
+    fn draw_header(&self, frame: &mut Frame, area: Rect) {
+        let state = match (&self.client, &self.connect_error) {
+            (Some(client), _) if client.is_connected() => Span::styled("connected", Color::Green),
+            (Some(_), _) => Span::styled("reconnecting", Color::Yellow),
+            (None, Some((error, _))) => Span::styled(format!("disconnected ({error})"), Color::Red),
+            (None, None) => Span::styled("connecting", Color::Yellow),
+        };
+
+        let line = Line::from(vec![
+            " Conductor ".bold().reversed(),
+            format!(" {} ", self.url).into(),
+            state,
+        ]);
+        frame.render_widget(Paragraph::new(line), area);
+    }
+
+    fn draw_library(&mut self, frame: &mut Frame, area: Rect) {
+        let running: HashSet<u32> = self.running.iter().copied().collect();
+
+        let rows: Vec<Row> = self
+            .visible_apps()
+            .iter()
+            .map(|app| {
+                let (kind, installed) = match &app.info {
+                    Some(info) => (
+                        type_name(info.app_type),
+                        if info.installed { "yes" } else { "" },
+                    ),
+                    None => ("", ""),
+                };
+                let state = if running.contains(&app.id) {
+                    Cell::from("running").green()
+                } else {
+                    Cell::from("")
+                };
+
+                Row::new(vec![
+                    Cell::from(app.id.to_string()),
+                    Cell::from(app.name()),
+                    Cell::from(kind),
+                    Cell::from(installed),
+                    state,
+                ])
+            })
+            .collect();
+
+        let title = if self.installed_only {
+            format!(" Library ({} installed) ", rows.len())
+        } else {
+            format!(" Library ({}) ", rows.len())
+        };
+
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(10),
+                Constraint::Min(20),
+                Constraint::Length(8),
+                Constraint::Length(9),
+                Constraint::Length(7),
+            ],
+        )
+        .header(Row::new(["ID", "Name", "Type", "Installed", "State"]).bold())
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .block(Block::bordered().title(title));
+
+        frame.render_stateful_widget(table, area, &mut self.table);
+    }
+
+    fn draw_running(&self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .running
+            .iter()
+            .map(|id| {
+                let name = self
+                    .apps
+                    .iter()
+                    .find(|app| app.id == *id)
+                    .map(|app| app.name())
+                    .unwrap_or_else(|| format!("App {id}"));
+                ListItem::new(format!("{id}  {name}"))
+            })
+            .collect();
+
+        frame.render_widget(
+            List::new(items).block(Block::bordered().title(" Running ")),
+            area,
+        );
+    }
+
+    fn draw_folders(&self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .folders
+            .iter()
+            .map(|folder| {
+                let mut title = format!(
+                    "{}. {}",
+                    folder.index.unwrap_or_default(),
+                    folder.folder_path.as_deref().unwrap_or_default()
+                );
+                if let Some(label) = &folder.user_label
+                    && !label.is_empty()
+                {
+                    title += &format!(" ({label})");
+                }
+                if folder.is_default == Some(true) {
+                    title += " [default]";
+                }
+
+                let space = if folder.is_mounted == Some(true) {
+                    format!(
+                        "   {} free of {}",
+                        format_size(folder.free_space.unwrap_or_default()),
+                        format_size(folder.total_capacity.unwrap_or_default())
+                    )
+                } else {
+                    "   not mounted".to_owned()
+                };
+
+                ListItem::new(vec![Line::from(title), Line::from(space).dark_gray()])
+            })
+            .collect();
+
+        frame.render_widget(
+            List::new(items).block(Block::bordered().title(" Install folders ")),
+            area,
+        );
+    }
+}
+
+async fn run_dashboard(
+    terminal: &mut DefaultTerminal,
+    dashboard: &mut Dashboard,
+    mut responses: UnboundedReceiver<Response>,
+) {
+    let mut events = EventStream::new();
+    let mut tick = tokio::time::interval(POLL_INTERVAL);
+    let mut connection = None;
+
+    dashboard.connect();
+
+    loop {
+        if connection.is_none()
+            && let Some(client) = &dashboard.client
+        {
+            connection = Some(client.connection());
+        }
+
+        _ = terminal.draw(|frame| dashboard.draw(frame));
+
+        tokio::select! {
+            Some(res) = responses.recv() => dashboard.handle_response(res),
+            changed = async { connection.as_mut().expect("not connected").changed().await }, if connection.is_some() => {
+                // Steam might have changed while we were gone
+                if changed.is_ok() && *connection.as_mut().expect("not connected").borrow_and_update() {
+                    dashboard.reload();
+                }
+            }
+            event = events.next() => {
+                match event {
+                    Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => {
+                        if !dashboard.handle_key(key) {
+                            return;
+                        }
+                    }
+                    Some(Ok(_)) => {}
+                    // Terminal closed
+                    Some(Err(_)) | None => return,
+                }
+            }
+            _ = tick.tick() => {
+                match (&dashboard.client, &dashboard.connect_error) {
+                    (Some(client), _) if client.is_connected() => {
+                        dashboard.call(GetRunningApps::default(), Response::RunningApps);
+                    }
+                    (None, Some((_, since)))
+                        if !dashboard.connecting && since.elapsed() >= RECONNECT_DELAY =>
+                    {
+                        dashboard.connect();
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+pub async fn run(args: TuiArgs, settings_path: &str) -> i32 {
+    let (url, secret) = args.connection.resolve(settings_path);
+    let options = ClientOptions {
+        secret,
+        ..Default::default()
+    };
+    let (responses_tx, responses_rx) = unbounded_channel();
+    let mut dashboard = Dashboard::new(url, options, responses_tx);
+
+    let mut terminal = ratatui::init();
+    run_dashboard(&mut terminal, &mut dashboard, responses_rx).await;
+    ratatui::restore();
+
+    0
+}
@@ -6,25 +6,176 @@
  *  License, v. 2.0. If a copy of the MPL was not distributed with this
  *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-use serde::Deserialize;
+
+//! Settings are merged from, in order of precedence: command line flags, `CONDUCTOR_*`
+//! environment variables, `settings.toml` and the defaults below.
+
+use crate::tokens::{HashedToken, SharedKey};
+use chrono::{DateTime, Utc};
+use clap::Args;
+use ipnet::IpNet;
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
 use thiserror::Error;
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
+#[serde(default)]
 pub struct AuthConfig {
     pub enabled: bool,
+    /// Plain tokens, kept working for older settings files
     pub tokens: Option<Vec<String>>,
+    /// Tokens added with `conductor token add`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hashed_tokens: Vec<HashedToken>,
+    /// Keys for signed requests, added with `conductor token add --signing`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub signing_keys: Vec<SharedKey>,
+    /// Let new devices get a token with `RequestPairing` and `ConfirmPairing`
+    pub pairing: bool,
+    pub lockout: LockoutConfig,
+}
+
+/// Limits on failed authentication, see [`crate::lockout`]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct LockoutConfig {
+    /// Failed attempts from one address before it's locked out, 0 turns this off
+    pub max_failures: u32,
+    /// Failed attempts from every address together before all of them are locked out, 0 turns
+    /// this off
+    pub global_max_failures: u32,
+    /// Seconds the first lockout lasts, each one after that is twice as long
+    pub lockout_secs: u64,
+    /// Longest lockout in seconds. Counters start over after this long without failures
+    pub max_lockout_secs: u64,
+    /// Networks that are never locked out
+    pub exempt: Vec<IpNet>,
 }
 
-#[derive(Deserialize)]
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        Self {
+            max_failures: 5,
+            global_max_failures: 50,
+            lockout_secs: 30,
+            max_lockout_secs: 3600,
+            // Loopback, so a typo in a local script can't lock out the machine itself
+            exempt: vec![
+                "127.0.0.0/8".parse().expect("valid network"),
+                "::1/128".parse().expect("valid network"),
+            ],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
 pub struct MainConfig {
     pub hostname: String,
     pub payload_path: String,
     pub replace_other_instances: bool,
+    pub steam_path: Option<String>,
+    pub disable_cef_on_exit: bool,
+    pub restart_steam: bool,
+    pub log_level: Option<String>,
+}
+
+impl Default for MainConfig {
+    fn default() -> Self {
+        Self {
+            hostname: "localhost:7355".to_owned(),
+            payload_path: "dist/payload.template.js".to_owned(),
+            replace_other_instances: false,
+            steam_path: None,
+            disable_cef_on_exit: false,
+            restart_steam: false,
+            log_level: None,
+        }
+    }
+}
+
+impl MainConfig {
+    /// Path to the payload, `payload_path` is relative to the executable.
+    pub fn payload_file(&self) -> std::io::Result<PathBuf> {
+        let exe = std::env::current_exe()?;
+        let dir = exe.parent().ok_or(std::io::ErrorKind::NotFound)?;
+        Ok(dir.join(&self.payload_path))
+    }
+
+    /// Level to log at, `log_level` wins over `RUST_LOG`.
+    pub fn log_level(&self) -> LevelFilter {
+        self.log_level
+            .as_deref()
+            .and_then(|level| LevelFilter::from_str(level).ok())
+            .unwrap_or_else(env_log_level)
+    }
 }
 
-#[derive(Deserialize)]
+/// Level from `RUST_LOG`, used before the settings are loaded and when `log_level` isn't set.
+pub fn env_log_level() -> LevelFilter {
+    std::env::var("RUST_LOG")
+        .ok()
+        .and_then(|level| LevelFilter::from_str(&level).ok())
+        .unwrap_or(LevelFilter::Info)
+}
+
+/// Which addresses can connect and which of them need a token
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Networks that can connect, every address if empty. Loopback always can since the payload
+    /// connects from it
+    pub allow: Vec<IpNet>,
+    /// Networks that can't connect, wins over `allow`
+    pub deny: Vec<IpNet>,
+    /// Networks that don't need a token when auth is on
+    pub skip_auth: Vec<IpNet>,
+    /// Start with auth off even if `hostname` accepts connections from other machines
+    pub allow_unauthenticated_remote: bool,
+    /// Web pages that can connect, like `http://localhost:3000`, or `*` for every page.
+    /// Clients that don't send an `Origin` header, like scripts and apps, always can
+    pub allowed_origins: Vec<String>,
+}
+
+/// Origin of the Steam page the payload runs in
+const STEAM_ORIGIN: &str = "https://steamloopback.host";
+
+impl NetworkConfig {
+    /// Returns `true` if `ip` can connect.
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        if ip.is_loopback() {
+            return true;
+        }
+        !self.deny.iter().any(|net| net.contains(&ip))
+            && (self.allow.is_empty() || self.allow.iter().any(|net| net.contains(&ip)))
+    }
+
+    /// Returns `true` if a web page from `origin` can connect. Without this check any page the
+    /// user visits could control Steam.
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        origin.eq_ignore_ascii_case(STEAM_ORIGIN)
+            || self
+                .allowed_origins
+                .iter()
+                .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(origin))
+    }
+
+    /// Returns `true` if requests from `ip` don't need a token.
+    pub fn skips_auth(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.skip_auth.iter().any(|net| net.contains(&ip))
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
+#[serde(default)]
 pub struct Config {
     pub conductor: MainConfig,
+    pub network: NetworkConfig,
     pub auth: Option<AuthConfig>,
 }
 
@@ -32,21 +183,620 @@ pub struct Config {
 pub enum LoadError {
     #[error("Error reading file: {0}")]
     ReadFile(std::io::Error),
-    #[error("Error deserializing file: {}", .0.message())]
+    #[error("Error deserializing file: {0}")]
     Deserialize(toml::de::Error),
+    #[error("Invalid value {value:?} for {var}, expected true or false")]
+    Env { var: &'static str, value: String },
+}
+
+/// A problem found by [`Config::validate`]
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("`hostname` must look like `host:port`, got {0:?}")]
+    InvalidHostname(String),
+    #[error("`log_level` must be one of off, error, warn, info, debug or trace, got {0:?}")]
+    InvalidLogLevel(String),
+    #[error("No tokens found, add some or set `auth.enabled` to false")]
+    NoTokens,
+    #[error("`auth.tokens` contains an empty token")]
+    EmptyToken,
+    #[error("Signing key {0:?} is empty")]
+    EmptySigningKey(String),
+    #[error("Token {0:?} has an invalid hash, revoke it and add a new one")]
+    InvalidTokenHash(String),
+    #[error("Token {0:?} has an unknown command {1:?} in `scopes`")]
+    UnknownScope(String, String),
+    #[error("Token {0:?} expires before its `not_before` time")]
+    EmptyValidity(String),
+    #[error("Can't read the payload at {0}: {1}")]
+    UnreadablePayload(String, std::io::Error),
+    #[error("`auth.lockout.lockout_secs` must be between 1 and `max_lockout_secs`")]
+    InvalidLockout,
+    #[error(
+        "`hostname` {0:?} accepts connections from other machines but auth is off, turn it on or \
+         set `allow_unauthenticated_remote = true` under [network]"
+    )]
+    UnauthenticatedRemote(String),
+}
+
+/// Shown instead of tokens and signing keys by `conductor config print`
+const REDACTED: &str = "<redacted>";
+
+/// Returns a commented settings file with the default values. Auth is turned on if `tokens`
+/// isn't empty.
+pub fn default_file(tokens: &[HashedToken]) -> Result<String, toml::ser::Error> {
+    let mut hashed_tokens = String::new();
+    for token in tokens {
+        hashed_tokens.push_str("\n[[auth.hashed_tokens]]\n");
+        hashed_tokens.push_str(&toml::to_string(token)?);
+    }
+
+    Ok(format!(
+        "[conductor]\n\
+         # Host name clients use to connect\n\
+         hostname = \"localhost:7355\"\n\
+         # Path to the payload js file, relative to the executable\n\
+         payload_path = \"dist/payload.template.js\"\n\
+         # If another instance is already running kill it\n\
+         replace_other_instances = false\n\
+         # Where Steam is installed, found automatically if not set\n\
+         # steam_path = \"/path/to/Steam\"\n\
+         # Turn off Steam's remote debugging when Conductor exits\n\
+         disable_cef_on_exit = false\n\
+         # Let Conductor start Steam, or restart it to turn on remote debugging\n\
+         restart_steam = false\n\
+         # One of off, error, warn, info, debug or trace, uses RUST_LOG or info if not set\n\
+         # log_level = \"info\"\n\
+         \n\
+         [network]\n\
+         # Networks that can connect like \"192.168.1.0/24\", every address if empty\n\
+         allow = []\n\
+         # Networks that can't connect, wins over `allow`\n\
+         deny = []\n\
+         # Networks that don't need a token when auth is on\n\
+         skip_auth = []\n\
+         # Start with auth off even if `hostname` accepts connections from other machines\n\
+         allow_unauthenticated_remote = false\n\
+         # Web pages that can connect like \"http://localhost:3000\", apps and scripts always can\n\
+         allowed_origins = []\n\
+         \n\
+         [auth]\n\
+         # Require a token in the `secret` field of every request, add them with\n\
+         # `conductor token add`\n\
+         enabled = {}\n\
+         # Let new devices ask for a token, they get it after entering the code shown in Steam\n\
+         pairing = false\n\
+         \n\
+         [auth.lockout]\n\
+         # Failed attempts from one address before it's locked out, 0 turns this off\n\
+         max_failures = 5\n\
+         # Failed attempts from every address together before all of them are locked out\n\
+         global_max_failures = 50\n\
+         # Seconds the first lockout lasts, each one after that is twice as long\n\
+         lockout_secs = 30\n\
+         # Longest lockout, counters start over after this long without failures\n\
+         max_lockout_secs = 3600\n\
+         # Networks that are never locked out\n\
+         exempt = [\"127.0.0.0/8\", \"::1/128\"]\n\
+         {}",
+        !tokens.is_empty(),
+        hashed_tokens
+    ))
+}
+
+/// Settings that can be changed on the command line, these win over the environment and the
+/// settings file.
+#[derive(Args, Default)]
+pub struct ConfigOverrides {
+    /// Address to listen on, like `localhost:7355`
+    #[arg(long)]
+    hostname: Option<String>,
+    /// Path to the payload, relative to the executable
+    #[arg(long, value_name = "PATH")]
+    payload_path: Option<String>,
+    /// Where Steam is installed
+    #[arg(long, value_name = "PATH")]
+    steam_path: Option<String>,
+    /// Kill other running instances
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    replace_other_instances: Option<bool>,
+    /// Turn off Steam's remote debugging on exit
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    disable_cef_on_exit: Option<bool>,
+    /// Let Conductor start or restart Steam
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    restart_steam: Option<bool>,
+    /// Require a token from clients
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    auth_enabled: Option<bool>,
+    /// One of off, error, warn, info, debug or trace
+    #[arg(long, value_name = "LEVEL")]
+    log_level: Option<String>,
+    /// Start with auth off even if the hostname accepts connections from other machines
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    allow_unauthenticated_remote: Option<bool>,
+}
+
+impl ConfigOverrides {
+    /// Returns the flags again, so a background server can be started with the same overrides.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut push = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                args.push(format!("--{name}={value}"));
+            }
+        };
+
+        push("hostname", self.hostname.clone());
+        push("payload-path", self.payload_path.clone());
+        push("steam-path", self.steam_path.clone());
+        push(
+            "replace-other-instances",
+            self.replace_other_instances.map(|v| v.to_string()),
+        );
+        push(
+            "disable-cef-on-exit",
+            self.disable_cef_on_exit.map(|v| v.to_string()),
+        );
+        push("restart-steam", self.restart_steam.map(|v| v.to_string()));
+        push("auth-enabled", self.auth_enabled.map(|v| v.to_string()));
+        push("log-level", self.log_level.clone());
+        push(
+            "allow-unauthenticated-remote",
+            self.allow_unauthenticated_remote.map(|v| v.to_string()),
+        );
+        args
+    }
+}
+
+fn env_bool(var: &'static str) -> Result<Option<bool>, LoadError> {
+    let Ok(value) = std::env::var(var) else {
+        return Ok(None);
+    };
+
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(Some(true)),
+        "0" | "false" | "no" | "off" => Ok(Some(false)),
+        _ => Err(LoadError::Env { var, value }),
+    }
+}
+
+fn is_valid_hostname(hostname: &str) -> bool {
+    let Some((host, port)) = hostname.rsplit_once(':') else {
+        return false;
+    };
+    let host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+
+    !host.is_empty() && !host.contains(char::is_whitespace) && port.parse::<u16>().is_ok()
+}
+
+/// Returns `true` if only this machine can connect to `hostname`. Host names other than
+/// `localhost` aren't looked up, so they count as remote.
+fn is_loopback_hostname(hostname: &str) -> bool {
+    let host = hostname.rsplit_once(':').map_or(hostname, |(host, _)| host);
+    let host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+
+    host.eq_ignore_ascii_case("localhost")
+        || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Checks the validity window and scopes of a hashed token or signing key.
+fn check_limits(
+    name: &str,
+    not_before: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+    scopes: &Option<Vec<String>>,
+    errors: &mut Vec<ConfigError>,
+) {
+    if let (Some(not_before), Some(expires_at)) = (not_before, expires_at)
+        && expires_at <= not_before
+    {
+        errors.push(ConfigError::EmptyValidity(name.to_owned()));
+    }
+
+    let schema = conductor_schema::schema();
+    for scope in scopes.iter().flatten() {
+        if schema.command(scope).is_none() {
+            errors.push(ConfigError::UnknownScope(name.to_owned(), scope.clone()));
+        }
+    }
 }
 
 impl Config {
+    /// Loads the settings file and applies the `CONDUCTOR_*` environment variables. A missing
+    /// file is the same as an empty one.
     pub fn load(path: &str) -> Result<Self, LoadError> {
         let file = match std::fs::read_to_string(path) {
             Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                log::debug!("No settings file at {path}, using defaults");
+                String::new()
+            }
             Err(e) => return Err(LoadError::ReadFile(e)),
         };
-        let cfg = match toml::from_str::<Config>(&file) {
+        let mut cfg = match toml::from_str::<Config>(&file) {
             Ok(f) => f,
             Err(e) => return Err(LoadError::Deserialize(e)),
         };
 
+        cfg.apply_env()?;
         Ok(cfg)
     }
+
+    fn apply_env(&mut self) -> Result<(), LoadError> {
+        let main = &mut self.conductor;
+        if let Ok(hostname) = std::env::var("CONDUCTOR_HOSTNAME") {
+            main.hostname = hostname;
+        }
+        if let Ok(payload_path) = std::env::var("CONDUCTOR_PAYLOAD_PATH") {
+            main.payload_path = payload_path;
+        }
+        if let Ok(steam_path) = std::env::var("CONDUCTOR_STEAM_PATH") {
+            main.steam_path = Some(steam_path);
+        }
+        if let Some(value) = env_bool("CONDUCTOR_REPLACE_OTHER_INSTANCES")? {
+            main.replace_other_instances = value;
+        }
+        if let Some(value) = env_bool("CONDUCTOR_DISABLE_CEF_ON_EXIT")? {
+            main.disable_cef_on_exit = value;
+        }
+        if let Some(value) = env_bool("CONDUCTOR_RESTART_STEAM")? {
+            main.restart_steam = value;
+        }
+        if let Ok(log_level) = std::env::var("CONDUCTOR_LOG_LEVEL") {
+            main.log_level = Some(log_level);
+        }
+
+        if let Some(value) = env_bool("CONDUCTOR_NETWORK_ALLOW_UNAUTHENTICATED_REMOTE")? {
+            self.network.allow_unauthenticated_remote = value;
+        }
+
+        if let Some(value) = env_bool("CONDUCTOR_AUTH_ENABLED")? {
+            self.auth.get_or_insert_default().enabled = value;
+        }
+        if let Ok(tokens) = std::env::var("CONDUCTOR_AUTH_TOKENS") {
+            self.auth.get_or_insert_default().tokens =
+                Some(tokens.split(',').map(|t| t.trim().to_owned()).collect());
+        }
+
+        Ok(())
+    }
+
+    /// Applies command line flags on top of the loaded settings.
+    pub fn apply(&mut self, overrides: &ConfigOverrides) {
+        let main = &mut self.conductor;
+        if let Some(hostname) = &overrides.hostname {
+            main.hostname.clone_from(hostname);
+        }
+        if let Some(payload_path) = &overrides.payload_path {
+            main.payload_path.clone_from(payload_path);
+        }
+        if let Some(steam_path) = &overrides.steam_path {
+            main.steam_path = Some(steam_path.clone());
+        }
+        if let Some(value) = overrides.replace_other_instances {
+            main.replace_other_instances = value;
+        }
+        if let Some(value) = overrides.disable_cef_on_exit {
+            main.disable_cef_on_exit = value;
+        }
+        if let Some(value) = overrides.restart_steam {
+            main.restart_steam = value;
+        }
+        if let Some(log_level) = &overrides.log_level {
+            main.log_level = Some(log_level.clone());
+        }
+        if let Some(value) = overrides.allow_unauthenticated_remote {
+            self.network.allow_unauthenticated_remote = value;
+        }
+        if let Some(value) = overrides.auth_enabled {
+            self.auth.get_or_insert_default().enabled = value;
+        }
+    }
+
+    /// Takes the settings from `new` that can change while the server is running: auth, network
+    /// lists, the log level and `disable_cef_on_exit`. Returns the names of the other settings that changed,
+    /// those keep their current values until Conductor is restarted.
+    pub fn reload(&mut self, new: Config) -> Vec<&'static str> {
+        let (main, new_main) = (&mut self.conductor, new.conductor);
+        let mut restart_required = Vec::new();
+
+        if main.hostname != new_main.hostname {
+            restart_required.push("hostname");
+        }
+        if main.payload_path != new_main.payload_path {
+            restart_required.push("payload_path");
+        }
+        if main.steam_path != new_main.steam_path {
+            restart_required.push("steam_path");
+        }
+        if main.replace_other_instances != new_main.replace_other_instances {
+            restart_required.push("replace_other_instances");
+        }
+        if main.restart_steam != new_main.restart_steam {
+            restart_required.push("restart_steam");
+        }
+
+        main.disable_cef_on_exit = new_main.disable_cef_on_exit;
+        main.log_level = new_main.log_level;
+        self.network = new.network;
+        self.auth = new.auth;
+
+        restart_required
+    }
+
+    /// Replaces every token and signing key so the settings can be shown without leaking them.
+    pub fn redact_secrets(&mut self) {
+        let Some(auth) = &mut self.auth else {
+            return;
+        };
+        if let Some(tokens) = &mut auth.tokens {
+            tokens.fill(REDACTED.to_owned());
+        }
+        for key in &mut auth.signing_keys {
+            key.key = REDACTED.to_owned();
+        }
+    }
+
+    /// Returns every problem that would stop the server from starting.
+    pub fn validate(&self) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+
+        if !is_valid_hostname(&self.conductor.hostname) {
+            errors.push(ConfigError::InvalidHostname(
+                self.conductor.hostname.clone(),
+            ));
+        }
+
+        if let Some(level) = &self.conductor.log_level
+            && LevelFilter::from_str(level).is_err()
+        {
+            errors.push(ConfigError::InvalidLogLevel(level.clone()));
+        }
+
+        if let Some(auth) = &self.auth
+            && auth.enabled
+        {
+            let tokens = auth.tokens.as_deref().unwrap_or_default();
+            if tokens.is_empty() && auth.hashed_tokens.is_empty() && auth.signing_keys.is_empty() {
+                errors.push(ConfigError::NoTokens);
+            }
+            if tokens.iter().any(String::is_empty) {
+                errors.push(ConfigError::EmptyToken);
+            }
+        }
+
+        for token in self.auth.iter().flat_map(|auth| &auth.hashed_tokens) {
+            if !token.is_valid() {
+                errors.push(ConfigError::InvalidTokenHash(token.name.clone()));
+            }
+            check_limits(
+                &token.name,
+                token.not_before,
+                token.expires_at,
+                &token.scopes,
+                &mut errors,
+            );
+        }
+        for key in self.auth.iter().flat_map(|auth| &auth.signing_keys) {
+            if key.key.is_empty() {
+                errors.push(ConfigError::EmptySigningKey(key.name.clone()));
+            }
+            check_limits(
+                &key.name,
+                key.not_before,
+                key.expires_at,
+                &key.scopes,
+                &mut errors,
+            );
+        }
+
+        let auth_enabled = self.auth.as_ref().is_some_and(|auth| auth.enabled);
+        if !auth_enabled
+            && !self.network.allow_unauthenticated_remote
+            && !is_loopback_hostname(&self.conductor.hostname)
+        {
+            errors.push(ConfigError::UnauthenticatedRemote(
+                self.conductor.hostname.clone(),
+            ));
+        }
+
+        if let Some(lockout) = self.auth.as_ref().map(|auth| &auth.lockout)
+            && !(1..=lockout.max_lockout_secs).contains(&lockout.lockout_secs)
+        {
+            errors.push(ConfigError::InvalidLockout);
+        }
+
+        let payload = self
+            .conductor
+            .payload_file()
+            .and_then(|path| std::fs::File::open(&path).map(|_| ()));
+        if let Err(e) = payload {
+            errors.push(ConfigError::UnreadablePayload(
+                self.conductor.payload_path.clone(),
+                e,
+            ));
+        }
+
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_file_uses_defaults() {
+        let cfg: Config = toml::from_str("[conductor]\nrestart_steam = true").unwrap();
+        assert_eq!(cfg.conductor.hostname, "localhost:7355");
+        assert!(cfg.conductor.restart_steam);
+        assert!(cfg.auth.is_none());
+
+        let cfg: Config = toml::from_str("").unwrap();
+        assert_eq!(cfg.conductor.payload_path, "dist/payload.template.js");
+    }
+
+    #[test]
+    fn flags_override_settings() {
+        let mut cfg = Config::default();
+        cfg.apply(&ConfigOverrides {
+            hostname: Some("0.0.0.0:80".to_owned()),
+            auth_enabled: Some(true),
+            ..Default::default()
+        });
+
+        assert_eq!(cfg.conductor.hostname, "0.0.0.0:80");
+        assert!(cfg.auth.is_some_and(|auth| auth.enabled));
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let mut cfg = Config::default();
+        cfg.conductor.hostname = "localhost".to_owned();
+        cfg.conductor.payload_path = "missing/payload.js".to_owned();
+        cfg.auth = Some(AuthConfig {
+            enabled: true,
+            tokens: Some(vec![]),
+            ..Default::default()
+        });
+
+        let errors = cfg.validate();
+        assert!(matches!(
+            errors.as_slice(),
+            [
+                ConfigError::InvalidHostname(_),
+                ConfigError::NoTokens,
+                ConfigError::UnreadablePayload(..),
+            ]
+        ));
+    }
+
+    #[test]
+    fn reload_keeps_restart_only_settings() {
+        let mut cfg = Config::default();
+        let mut new = Config::default();
+        new.conductor.hostname = "0.0.0.0:7355".to_owned();
+        new.conductor.log_level = Some("debug".to_owned());
+        new.auth = Some(AuthConfig {
+            enabled: true,
+            tokens: Some(vec!["new".to_owned()]),
+            ..Default::default()
+        });
+
+        assert_eq!(cfg.reload(new), ["hostname"]);
+        assert_eq!(cfg.conductor.hostname, "localhost:7355");
+        assert_eq!(cfg.conductor.log_level(), LevelFilter::Debug);
+        assert!(
+            cfg.auth
+                .is_some_and(|auth| auth.tokens == Some(vec!["new".to_owned()]))
+        );
+    }
+
+    #[test]
+    fn default_file_matches_defaults() {
+        let cfg: Config = toml::from_str(&default_file(&[]).unwrap()).unwrap();
+        assert!(cfg.conductor == MainConfig::default());
+        assert_eq!(cfg.network, NetworkConfig::default());
+        assert!(
+            cfg.auth
+                .is_some_and(|auth| !auth.enabled && auth.lockout == LockoutConfig::default())
+        );
+
+        let (token, secret) = HashedToken::generate("test".to_owned(), None, None, None);
+        let cfg: Config =
+            toml::from_str(&default_file(std::slice::from_ref(&token)).unwrap()).unwrap();
+        let auth = cfg.auth.as_ref().unwrap();
+        assert!(auth.enabled);
+        assert_eq!(auth.hashed_tokens, [token]);
+        assert!(auth.hashed_tokens[0].matches(&secret));
+        assert!(
+            cfg.validate()
+                .iter()
+                .all(|e| matches!(e, ConfigError::UnreadablePayload(..)))
+        );
+    }
+
+    #[test]
+    fn redacts_tokens() {
+        let mut cfg = Config {
+            auth: Some(AuthConfig {
+                enabled: true,
+                tokens: Some(vec!["hunter2".to_owned(), "1234".to_owned()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        cfg.redact_secrets();
+
+        let printed = toml::to_string(&cfg).unwrap();
+        assert!(!printed.contains("hunter2"));
+        assert!(printed.contains(REDACTED));
+    }
+
+    #[test]
+    fn checks_hostnames() {
+        assert!(is_valid_hostname("localhost:7355"));
+        assert!(is_valid_hostname("[::1]:7355"));
+        assert!(!is_valid_hostname("localhost"));
+        assert!(!is_valid_hostname(":7355"));
+        assert!(!is_valid_hostname("localhost:99999"));
+
+        assert!(is_loopback_hostname("localhost:7355"));
+        assert!(is_loopback_hostname("127.0.0.2:7355"));
+        assert!(is_loopback_hostname("[::1]:7355"));
+        assert!(!is_loopback_hostname("0.0.0.0:7355"));
+        assert!(!is_loopback_hostname("steam-deck.local:7355"));
+    }
+
+    #[test]
+    fn checks_networks() {
+        let network = NetworkConfig {
+            allow: vec!["192.168.1.0/24".parse().unwrap()],
+            deny: vec!["192.168.1.13/32".parse().unwrap()],
+            skip_auth: vec!["192.168.1.0/28".parse().unwrap()],
+            ..Default::default()
+        };
+        let ip = |ip: &str| ip.parse::<IpAddr>().unwrap();
+
+        assert!(network.allows(ip("192.168.1.20")));
+        assert!(network.allows(ip("::ffff:192.168.1.20")));
+        assert!(network.allows(ip("127.0.0.1")));
+        assert!(!network.allows(ip("192.168.1.13")));
+        assert!(!network.allows(ip("10.0.0.1")));
+        assert!(NetworkConfig::default().allows(ip("10.0.0.1")));
+
+        assert!(network.skips_auth(ip("192.168.1.2")));
+        assert!(!network.skips_auth(ip("192.168.1.20")));
+
+        let network = NetworkConfig {
+            allowed_origins: vec!["http://localhost:3000".to_owned()],
+            ..Default::default()
+        };
+        assert!(network.allows_origin(STEAM_ORIGIN));
+        assert!(network.allows_origin("http://LOCALHOST:3000"));
+        assert!(!network.allows_origin("https://evil.example"));
+        assert!(!network.allows_origin("null"));
+        assert!(!NetworkConfig::default().allows_origin("http://localhost:3000"));
+
+        let mut cfg = Config::default();
+        cfg.conductor.hostname = "0.0.0.0:7355".to_owned();
+        assert!(
+            cfg.validate()
+                .iter()
+                .any(|e| matches!(e, ConfigError::UnauthenticatedRemote(_)))
+        );
+        cfg.network.allow_unauthenticated_remote = true;
+        assert!(
+            !cfg.validate()
+                .iter()
+                .any(|e| matches!(e, ConfigError::UnauthenticatedRemote(_)))
+        );
+    }
 }
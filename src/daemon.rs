@@ -0,0 +1,445 @@
+/*
+ *  daemon.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Instance lock, pidfile and the local control channel used by `conductor stop` and
+//! `conductor status`. The control channel listens on a random loopback port written to the
+//! pidfile together with a token, so only users that can read the pidfile can control the server.
+
+use crate::secrets::generate_secret;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// How long to wait for the control channel to answer
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Error, Debug)]
+pub enum DaemonError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("Conductor is already running with pid {0}")]
+    AlreadyRunning(u32),
+    #[error("Conductor is not running")]
+    NotRunning,
+    #[error("Invalid pidfile at {}", .0.display())]
+    InvalidPidfile(PathBuf),
+    #[error("Control channel error: {0}")]
+    Control(String),
+    #[error("Conductor did not stop after {} seconds", .0.as_secs())]
+    StopTimeout(Duration),
+    #[error("Conductor exited while starting, check the log at {}", .0.display())]
+    StartFailed(PathBuf),
+    #[error("{} belongs to another user, remove it or set XDG_RUNTIME_DIR", .0.display())]
+    ForeignRuntimeDir(PathBuf),
+}
+
+/// Where the lock file, pidfile and daemon log live. The temp folder is shared on Unix, so the
+/// fallback has the user ID in its name.
+pub fn runtime_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        return PathBuf::from(dir).join("conductor");
+    }
+
+    cfg_if::cfg_if! {
+        if #[cfg(unix)] {
+            // SAFETY: geteuid has no preconditions and can't fail
+            let uid = unsafe { libc::geteuid() };
+            std::env::temp_dir().join(format!("conductor-{uid}"))
+        } else {
+            std::env::temp_dir().join("conductor")
+        }
+    }
+}
+
+/// Makes sure `dir` is a folder of the current user, so nobody else can hold the lock or swap out
+/// the pidfile. A missing folder is fine.
+fn check_owner(dir: &Path) -> Result<(), DaemonError> {
+    cfg_if::cfg_if! {
+        if #[cfg(unix)] {
+            use std::os::unix::fs::MetadataExt;
+
+            let meta = match dir.symlink_metadata() {
+                Ok(meta) => meta,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+                Err(e) => return Err(e.into()),
+            };
+            // SAFETY: geteuid has no preconditions and can't fail
+            if !meta.is_dir() || meta.uid() != unsafe { libc::geteuid() } {
+                return Err(DaemonError::ForeignRuntimeDir(dir.to_path_buf()));
+            }
+        } else {
+            _ = dir;
+        }
+    }
+
+    Ok(())
+}
+
+fn lock_path(dir: &Path) -> PathBuf {
+    dir.join("conductor.lock")
+}
+
+fn pid_path(dir: &Path) -> PathBuf {
+    dir.join("conductor.pid")
+}
+
+/// Default log file for `start --daemon`
+pub fn log_path() -> PathBuf {
+    runtime_dir().join("conductor.log")
+}
+
+fn create_runtime_dir() -> Result<PathBuf, DaemonError> {
+    let dir = runtime_dir();
+    std::fs::create_dir_all(&dir)?;
+    check_owner(&dir)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+
+    Ok(dir)
+}
+
+/// Contents of the pidfile: the pid on the first line so other tools can read it, then the
+/// control channel address and token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PidFile {
+    pub pid: u32,
+    pub control_addr: SocketAddr,
+    token: String,
+}
+
+impl PidFile {
+    fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.lines();
+        Some(PidFile {
+            pid: lines.next()?.trim().parse().ok()?,
+            control_addr: lines.next()?.trim().parse().ok()?,
+            token: lines.next()?.trim().to_owned(),
+        })
+    }
+
+    fn read(dir: &Path) -> Result<Self, DaemonError> {
+        let path = pid_path(dir);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(DaemonError::NotRunning);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        PidFile::parse(&text).ok_or(DaemonError::InvalidPidfile(path))
+    }
+
+    fn write(&self, dir: &Path) -> Result<(), DaemonError> {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let mut file = options.open(pid_path(dir))?;
+        std::io::Write::write_all(
+            &mut file,
+            format!("{}\n{}\n{}\n", self.pid, self.control_addr, self.token).as_bytes(),
+        )?;
+        Ok(())
+    }
+}
+
+/// Returns `true` if another process holds the instance lock.
+fn is_locked(dir: &Path) -> Result<bool, DaemonError> {
+    let file = match File::open(lock_path(dir)) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+
+    match file.try_lock() {
+        Ok(()) => Ok(false),
+        Err(TryLockError::WouldBlock) => Ok(true),
+        Err(TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ControlRequest {
+    token: String,
+    command: String,
+}
+
+/// Lock held by the running server. Only one server can run for each user.
+pub struct Instance {
+    _lock: File,
+    dir: PathBuf,
+    started: Instant,
+}
+
+impl Instance {
+    /// Takes the instance lock, failing if another server is running.
+    pub fn acquire() -> Result<Self, DaemonError> {
+        let dir = create_runtime_dir()?;
+        let lock = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(lock_path(&dir))?;
+
+        match lock.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let pid = PidFile::read(&dir).map(|p| p.pid).unwrap_or_default();
+                return Err(DaemonError::AlreadyRunning(pid));
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        Ok(Instance {
+            _lock: lock,
+            dir,
+            started: Instant::now(),
+        })
+    }
+
+    /// Opens the control channel and writes the pidfile. `stop` requests are sent to `exit_tx`
+    /// like any other clean exit.
+    pub async fn listen(
+        &self,
+        hostname: String,
+        exit_tx: UnboundedSender<bool>,
+    ) -> Result<(), DaemonError> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let pidfile = PidFile {
+            pid: std::process::id(),
+            control_addr: listener.local_addr()?,
+            token: generate_secret(),
+        };
+        pidfile.write(&self.dir)?;
+        log::debug!("Control channel listening on {}", pidfile.control_addr);
+
+        let started = self.started;
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let response = |command: &str| match command {
+                    "stop" => {
+                        log::info!("Stop requested");
+                        _ = exit_tx.send(true);
+                        json!({ "success": true })
+                    }
+                    "status" => json!({
+                        "success": true,
+                        "pid": pidfile.pid,
+                        "hostname": hostname,
+                        "uptime": started.elapsed().as_secs(),
+                    }),
+                    _ => json!({ "success": false, "error": format!("Unknown command {command}") }),
+                };
+
+                handle_control(stream, &pidfile.token, response).await;
+            }
+        });
+
+        Ok(())
+    }
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        _ = std::fs::remove_file(pid_path(&self.dir));
+    }
+}
+
+async fn handle_control(
+    stream: TcpStream,
+    token: &str,
+    response: impl FnOnce(&str) -> serde_json::Value,
+) {
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+
+    let mut reader = BufReader::new(reader);
+    let read = reader.read_line(&mut line);
+    if !matches!(tokio::time::timeout(CONTROL_TIMEOUT, read).await, Ok(Ok(_))) {
+        return;
+    }
+
+    let res = match serde_json::from_str::<ControlRequest>(&line) {
+        Ok(req) if req.token == token => response(&req.command),
+        Ok(_) => json!({ "success": false, "error": "Wrong token" }),
+        Err(e) => json!({ "success": false, "error": format!("Invalid request: {e}") }),
+    };
+
+    _ = writer.write_all(format!("{res}\n").as_bytes()).await;
+}
+
+/// Sends a command to the running server over the control channel.
+async fn send_control(command: &str) -> Result<serde_json::Value, DaemonError> {
+    let dir = runtime_dir();
+    check_owner(&dir)?;
+    if !is_locked(&dir)? {
+        return Err(DaemonError::NotRunning);
+    }
+    let pidfile = PidFile::read(&dir)?;
+
+    let exchange = async {
+        let mut stream = TcpStream::connect(pidfile.control_addr).await?;
+        let req = serde_json::to_string(&ControlRequest {
+            token: pidfile.token.clone(),
+            command: command.to_owned(),
+        })
+        .map_err(|e| DaemonError::Control(e.to_string()))?;
+        stream.write_all(format!("{req}\n").as_bytes()).await?;
+
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).await?;
+        serde_json::from_str::<serde_json::Value>(&line)
+            .map_err(|e| DaemonError::Control(e.to_string()))
+    };
+
+    let res = tokio::time::timeout(CONTROL_TIMEOUT, exchange)
+        .await
+        .map_err(|_| DaemonError::Control("no response".to_owned()))??;
+
+    if res.get("success").and_then(|s| s.as_bool()) != Some(true) {
+        let error = res
+            .get("error")
+            .and_then(|e| e.as_str())
+            .unwrap_or("failed");
+        return Err(DaemonError::Control(error.to_owned()));
+    }
+    Ok(res)
+}
+
+/// Returns the status reported by the running server.
+pub async fn status() -> Result<serde_json::Value, DaemonError> {
+    send_control("status").await
+}
+
+/// Asks the running server to shut down and waits until it's gone.
+pub async fn stop(timeout: Duration) -> Result<(), DaemonError> {
+    send_control("stop").await?;
+
+    let dir = runtime_dir();
+    let deadline = Instant::now() + timeout;
+    while is_locked(&dir)? {
+        if Instant::now() > deadline {
+            return Err(DaemonError::StopTimeout(timeout));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    Ok(())
+}
+
+/// Starts the server in the background with its output going to `log` and the given config
+/// `overrides`, and waits until its control channel is up. Returns the new server's pid.
+pub async fn spawn(
+    settings_path: &str,
+    log: &Path,
+    overrides: &[String],
+) -> Result<u32, DaemonError> {
+    let dir = create_runtime_dir()?;
+    if is_locked(&dir)? {
+        let pid = PidFile::read(&dir).map(|p| p.pid).unwrap_or_default();
+        return Err(DaemonError::AlreadyRunning(pid));
+    }
+
+    let log_file = OpenOptions::new().create(true).append(true).open(log)?;
+
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command
+        .args(["--settings-path", settings_path, "start"])
+        .args(overrides)
+        .stdin(Stdio::null())
+        .stdout(log_file.try_clone()?)
+        .stderr(log_file);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // Leave the terminal's process group so Ctrl+C and hangups don't reach the server
+        command.process_group(0);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const DETACHED_PROCESS: u32 = 0x8;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x200;
+        command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    }
+
+    let mut child = command.spawn()?;
+
+    let deadline = Instant::now() + CONTROL_TIMEOUT;
+    loop {
+        if child.try_wait()?.is_some() {
+            return Err(DaemonError::StartFailed(log.to_owned()));
+        }
+        if let Ok(pidfile) = PidFile::read(&dir)
+            && pidfile.pid == child.id()
+            && status().await.is_ok()
+        {
+            return Ok(child.id());
+        }
+        if Instant::now() > deadline {
+            return Err(DaemonError::Control(format!(
+                "the server did not answer, check the log at {}",
+                log.display()
+            )));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pidfile_round_trip() {
+        let pidfile = PidFile {
+            pid: 1234,
+            control_addr: "127.0.0.1:40000".parse().unwrap(),
+            token: "secret".to_owned(),
+        };
+
+        assert_eq!(
+            PidFile::parse("1234\n127.0.0.1:40000\nsecret\n"),
+            Some(pidfile)
+        );
+        assert_eq!(PidFile::parse("1234\n"), None);
+        assert_eq!(PidFile::parse("abc\n127.0.0.1:1\nx\n"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn checks_runtime_dir_owner() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(check_owner(dir.path()).is_ok());
+        assert!(check_owner(&dir.path().join("missing")).is_ok());
+
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(dir.path(), &link).unwrap();
+        assert!(matches!(
+            check_owner(&link),
+            Err(DaemonError::ForeignRuntimeDir(_))
+        ));
+    }
+}
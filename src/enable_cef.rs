@@ -7,45 +7,19 @@
  *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use cfg_if::cfg_if;
-use std::fs::File;
-use std::io::ErrorKind;
-#[allow(unused_imports)] // only used on Windows
-use std::path::Path;
-
-pub fn enable_cef_debugging() -> Result<(), std::io::Error> {
-    #[allow(unused_assignments)] // might be None on some platforms
-    let mut file_path = None;
-
-    cfg_if! {
-        if #[cfg(windows)] {
-            file_path = Some(Path::new(r"C:\Program Files (x86)\Steam\.cef-enable-remote-debugging"));
-        } else if #[cfg(target_os = "linux")] {
-            #[allow(deprecated)] // behavior fixed in rust 1.85, will be un-deprecated
-            let Some(mut home_dir) = std::env::home_dir() else {
-                return Err(std::io::Error::new(ErrorKind::NotFound, "Failed to get home directory"));
-            };
-            home_dir.push(".steam/steam/.cef-enable-remote-debugging");
-            file_path = Some(home_dir.as_path());
-        } else if #[cfg(target_os = "macos")] {
-            #[allow(deprecated)]
-            let Some(mut home_dir) = std::env::home_dir() else {
-                return Err(std::io::Error::new(ErrorKind::NotFound, "Failed to get home directory"));
-            };
-            home_dir.push("Library/Application Support/Steam/.cef-enable-remote-debugging");
-            file_path = Some(home_dir.as_path());
-        }
-    }
+use crate::steam::Steam;
 
-    let Some(file_path) = file_path else {
-        return Err(std::io::Error::new(
-            ErrorKind::Unsupported,
-            "Steam is not supported on this platform",
-        ));
-    };
+/// Written to marker files Conductor creates. Steam only checks if the file exists, the contents
+/// tell us it's safe to delete without touching a marker the user made on purpose.
+const MARKER_CONTENTS: &str = "Created by Conductor, removed by `conductor disable-cef`\n";
+
+/// Creates the CEF debugging marker if it's missing. Returns `true` if it was just created, which
+/// means a running Steam client has to restart before the debugger port opens.
+pub fn enable_cef_debugging(steam: &Steam) -> Result<bool, std::io::Error> {
+    let file_path = steam.cef_debugging_marker();
 
     if !file_path.exists() {
-        if let Err(e) = File::create(file_path) {
+        if let Err(e) = std::fs::write(&file_path, MARKER_CONTENTS) {
             let path_string = file_path.to_string_lossy();
             log::error!(
                 "Failed to enable CEF debugging. Try creating an empty file manually at {}",
@@ -54,8 +28,88 @@ pub fn enable_cef_debugging() -> Result<(), std::io::Error> {
             return Err(e);
         }
 
-        log::warn!("CEF remote debugging was not enabled, restart Steam if it's running!");
+        log::info!("Enabled CEF remote debugging");
+        return Ok(true);
     }
 
-    Ok(())
+    Ok(false)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DisableOutcome {
+    /// The marker file was deleted
+    Removed,
+    /// There was no marker file
+    NotEnabled,
+    /// The marker file wasn't created by Conductor so it was left alone
+    KeptUserFile,
+}
+
+/// Removes the CEF debugging marker if Conductor created it, or any marker if `force` is set.
+/// Steam keeps the debugger port open until it restarts.
+pub fn disable_cef_debugging(steam: &Steam, force: bool) -> Result<DisableOutcome, std::io::Error> {
+    let file_path = steam.cef_debugging_marker();
+
+    let contents = match std::fs::read(&file_path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(DisableOutcome::NotEnabled);
+        }
+        Err(e) => return Err(e),
+    };
+
+    if !force && contents != MARKER_CONTENTS.as_bytes() {
+        return Ok(DisableOutcome::KeptUserFile);
+    }
+
+    std::fs::remove_file(&file_path)?;
+    Ok(DisableOutcome::Removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steam(dir: &tempfile::TempDir) -> Steam {
+        std::fs::create_dir(dir.path().join("config")).unwrap();
+        Steam::locate(Some(dir.path())).unwrap()
+    }
+
+    #[test]
+    fn removes_its_own_marker() {
+        let dir = tempfile::tempdir().unwrap();
+        let steam = steam(&dir);
+
+        assert_eq!(
+            disable_cef_debugging(&steam, false).unwrap(),
+            DisableOutcome::NotEnabled
+        );
+        assert!(enable_cef_debugging(&steam).unwrap());
+        assert!(!enable_cef_debugging(&steam).unwrap());
+        assert_eq!(
+            disable_cef_debugging(&steam, false).unwrap(),
+            DisableOutcome::Removed
+        );
+        assert!(!steam.cef_debugging_marker().exists());
+    }
+
+    #[test]
+    fn keeps_user_markers() {
+        let dir = tempfile::tempdir().unwrap();
+        let steam = steam(&dir);
+        std::fs::write(steam.cef_debugging_marker(), "").unwrap();
+
+        assert!(!enable_cef_debugging(&steam).unwrap());
+        assert_eq!(
+            disable_cef_debugging(&steam, false).unwrap(),
+            DisableOutcome::KeptUserFile
+        );
+        assert!(steam.cef_debugging_marker().exists());
+
+        assert_eq!(
+            disable_cef_debugging(&steam, true).unwrap(),
+            DisableOutcome::Removed
+        );
+        assert!(!steam.cef_debugging_marker().exists());
+    }
 }
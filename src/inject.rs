@@ -7,6 +7,7 @@
  *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
+use crate::steam;
 use futures_util::{SinkExt, StreamExt};
 use reqwest::{Error, Response};
 use serde::Deserialize;
@@ -51,8 +52,12 @@ pub enum DebuggerUrlError {
     CreateClientFailed,
     #[error("Max retries reached")]
     MaxRetriesReached,
+    #[error("Steam is not running")]
+    SteamNotRunning,
 }
 
+/// Fetches the debugger URL for Steam's shared JS context. Gives up early if the Steam process
+/// goes away instead of retrying until `max_tries` runs out.
 pub async fn try_get_debugger_url(max_tries: Option<u32>) -> Result<String, DebuggerUrlError> {
     let mut tries_left = max_tries.unwrap_or(0);
 
@@ -66,7 +71,11 @@ pub async fn try_get_debugger_url(max_tries: Option<u32>) -> Result<String, Debu
             if let Some(url) = find_url(res).await {
                 return Ok(url);
             } else {
-                log::info!("Connection to Steam client failed, retrying...");
+                if !steam::is_running() {
+                    return Err(DebuggerUrlError::SteamNotRunning);
+                }
+
+                log::info!("Steam is running but its debugger is not reachable yet, retrying...");
                 tokio::time::sleep(Duration::from_millis(1000)).await;
 
                 if max_tries.is_some() {
@@ -169,7 +178,7 @@ pub async fn kill_running_instance(url: &str, max_tries: u32) -> Result<(), Inje
         let json = serde_json::ser::to_string(&msg).unwrap();
 
         for _ in 0..max_tries {
-            if let Ok(_) = tx.send(Message::from(json.to_owned())).await {
+            if tx.send(Message::from(json.to_owned())).await.is_ok() {
                 log::debug!("Terminated other instance");
                 return Ok(());
             } else {
@@ -199,7 +208,7 @@ pub async fn inject_payload(url: &str, payload: &str, max_tries: u32) -> Result<
         let json = serde_json::ser::to_string(&msg).unwrap();
 
         for _ in 0..max_tries {
-            if let Ok(_) = tx.send(Message::from(json.to_owned())).await {
+            if tx.send(Message::from(json.to_owned())).await.is_ok() {
                 log::debug!("Injected payload");
                 return Ok(());
             } else {
@@ -0,0 +1,395 @@
+/*
+ *  commands.ts
+ *  Generated from schema/commands.json by `cargo run -p conductor-schema`, do not edit.
+ */
+
+import type { AppType, UIMode } from "./steam";
+
+/**
+ * Commands handled by the payload inside Steam.
+ *
+ * Commands with a `fields` argument support output filtering like this:
+ *
+ * ```typescript
+ * {
+ *     fields: ['driveName', 'userLabel'],
+ * }
+ *
+ * // returns:
+ * [
+ *     { driveName: 'C:', userLabel: 'main', },
+ * ]
+ * ```
+ */
+export type RpcCommands = {
+    /**
+     * Adds a shortcut (non-Steam game) to your library.
+     */
+    AddShortcut: {
+        args: {
+            /** game title (default: exe name without extension) */
+            name?: string;
+            /** path to the game executable */
+            exe: string;
+            /** launch options for the game (default: []) */
+            launchOptions?: string[];
+            /** path to the game icon (.jpg, .png, .exe or .bmp, default: game exe) */
+            icon?: string;
+            /** path to starting folder (default: the exe's parent folder) */
+            startDir?: string;
+        };
+        returns: {
+            /** generated app ID */
+            appId: number;
+        };
+    };
+    /**
+     * Removes a shortcut from your library.
+     */
+    RemoveShortcut: {
+        args: {
+            /** app ID to remove */
+            appId: number;
+        };
+        returns: {};
+    };
+    /**
+     * Opens a dialog to install a single app.
+     */
+    InstallApp: {
+        args: {
+            /** app ID to install */
+            appId: number;
+            /** index of the folder to install to, asks the user if not set */
+            folderIdx?: number;
+        };
+        returns: {};
+    };
+    /**
+     * Opens a dialog to install one or more apps. If you pass more than one ID Steam will show them all in one dialog.
+     */
+    InstallApps: {
+        args: {
+            /** app IDs to install */
+            appIds: number[];
+            /** index of the folder to install to, asks the user if not set */
+            folderIdx?: number;
+        };
+        returns: {};
+    };
+    /**
+     * Opens a dialog to uninstall a single app. If `autoConfirm` is `true` uninstalls without asking.
+     */
+    UninstallApp: {
+        args: {
+            /** app ID to remove */
+            appId: number;
+            /** uninstall without asking? */
+            autoConfirm?: boolean;
+        };
+        returns: {};
+    };
+    /**
+     * Opens a dialog to uninstall one or more apps. If `autoConfirm` is `true` uninstalls without asking.
+     */
+    UninstallApps: {
+        args: {
+            /** app IDs to remove */
+            appIds: number[];
+            /** uninstall without asking? */
+            autoConfirm?: boolean;
+        };
+        returns: {};
+    };
+    /**
+     * Starts an installed app or shortcut.
+     */
+    RunApp: {
+        args: {
+            /** app ID to run */
+            appId: number;
+        };
+        returns: {};
+    };
+    /**
+     * Stops a running app or shortcut.
+     */
+    TerminateApp: {
+        args: {
+            /** app ID to stop */
+            appId: number;
+        };
+        returns: {};
+    };
+    /**
+     * Returns the IDs for all the apps in your Steam library that match the filters.
+     */
+    GetApps: {
+        args: {
+            /** app types to include in the results (default: all types) */
+            typeFilter?: AppType[];
+            /** only return apps installed on this device (default: false) */
+            installedOnly?: boolean;
+        };
+        returns: {
+            /** library app IDs */
+            appIds: number[];
+        };
+    };
+    /**
+     * Returns the IDs of apps and shortcuts that are running right now.
+     */
+    GetRunningApps: {
+        args: {};
+        returns: {
+            /** running app IDs */
+            appIds: number[];
+        };
+    };
+    /**
+     * Sets Steam's UI mode. **This will disconnect Conductor for 1-2 seconds on Windows** if the mode changes.
+     */
+    SetUIMode: {
+        args: {
+            /** new UI mode */
+            mode: UIMode;
+        };
+        returns: {};
+    };
+    /**
+     * Returns Steam's UI mode.
+     */
+    GetUIMode: {
+        args: {};
+        returns: {
+            /** current UI mode */
+            mode: UIMode;
+        };
+    };
+    /**
+     * Returns info about an app in your library.
+     */
+    GetAppInfo: {
+        args: {
+            /** the app ID you're looking for */
+            appId: number;
+        };
+        returns: {
+            /** app ID */
+            id: number;
+            /** the app's type (game, software, shortcut...) */
+            type: AppType;
+            /** is installed on this device? */
+            installed: boolean;
+            /** app display name */
+            displayName: string;
+            /** the app's store tags */
+            storeTags: number[];
+            /** launch options passed to the app */
+            launchOptions: string;
+            /** the file this shortcut points to, only set for shortcuts */
+            shortcutExe?: string;
+            /** folder used to start the app, only set for shortcuts */
+            startDir?: string;
+            /** developer name on Steam, not set for shortcuts */
+            developerName?: string;
+            /** index of the folder this app is installed to, not set for shortcuts */
+            installFolder?: number;
+        };
+    };
+    /**
+     * Returns the localized name of a store tag.
+     */
+    GetTagName: {
+        args: {
+            /** the tag's ID */
+            tagId: number;
+        };
+        returns: {
+            /** the tag's name */
+            name: string;
+        };
+    };
+    /**
+     * Returns the localized names for a list of store tags.
+     */
+    GetTagNames: {
+        args: {
+            /** the tag IDs */
+            tagIds: number[];
+        };
+        returns: {
+            /** names for each ID */
+            names: string[];
+        };
+    };
+    /**
+     * Returns info about library folders on this device.
+     */
+    GetInstallFolders: {
+        args: {
+            /** filter used to pick some fields (default: all fields) */
+            fields?: InstallFolderField[];
+        };
+        returns: {
+            /** filtered data for every folder */
+            folders: InstallFolder[];
+        };
+    };
+};
+
+/**
+ * Commands handled by the Conductor server itself. They work even when Steam is not connected.
+ */
+export type ServerCommands = {
+    /**
+     * Lists library artwork set for an app or shortcut.
+     */
+    GetArtwork: {
+        args: {
+            /** app ID to look up */
+            appId: number;
+            /** Steam user that owns the artwork (default: the user that logged in last) */
+            accountId?: number;
+        };
+        returns: {
+            /** artwork files found for the app */
+            artwork: Artwork[];
+        };
+    };
+    /**
+     * Copies or links an image into the library grid folder, replacing any artwork of the same kind. Only PNG and JPEG images are supported. Steam picks up new artwork after a restart.
+     */
+    SetArtwork: {
+        args: {
+            /** app ID the artwork is for */
+            appId: number;
+            /** which library slot to fill */
+            kind: ArtworkKind;
+            /** path to the image on the Conductor machine */
+            path: string;
+            /** link to the image instead of copying it, later changes to the file show up in Steam (default: false) */
+            link?: boolean;
+            /** Steam user that owns the artwork (default: the user that logged in last) */
+            accountId?: number;
+        };
+        returns: {
+            /** path to the new artwork file */
+            path: string;
+        };
+    };
+    /**
+     * Removes library artwork for an app.
+     */
+    RemoveArtwork: {
+        args: {
+            /** app ID to remove artwork from */
+            appId: number;
+            /** which library slot to clear (default: all of them) */
+            kind?: ArtworkKind;
+            /** Steam user that owns the artwork (default: the user that logged in last) */
+            accountId?: number;
+        };
+        returns: {
+            /** paths to the deleted files */
+            removed: string[];
+        };
+    };
+    /**
+     * Creates a short-lived session token with the same scopes as the token the request was sent with. Session tokens stop working when they expire or when that token is revoked, and can't be refreshed themselves.
+     */
+    RefreshToken: {
+        args: {
+            /** seconds until the session token expires, at most 86400 (default: 3600) */
+            ttl?: number;
+        };
+        returns: {
+            /** session token to send in the `secret` field */
+            token: string;
+            /** when the session token expires, as an RFC 3339 date */
+            expiresAt: string;
+        };
+    };
+    /**
+     * Asks to pair a new device. Conductor shows a 6 digit code in its log and in Steam, send it with `ConfirmPairing` to get a token. Works without a token when `pairing` is on.
+     */
+    RequestPairing: {
+        args: {
+            /** name for the device, used as the token's name */
+            deviceName: string;
+            /** commands the token can run (default: all of them) */
+            scopes?: string[];
+        };
+        returns: {
+            /** ID to send with the code */
+            pairingId: string;
+            /** when the code stops working, as an RFC 3339 date */
+            expiresAt: string;
+        };
+    };
+    /**
+     * Finishes pairing with the code shown by Conductor. The token is saved to the settings file and only returned once. Three wrong codes cancel the request.
+     */
+    ConfirmPairing: {
+        args: {
+            /** ID returned by `RequestPairing` */
+            pairingId: string;
+            /** code shown by Conductor */
+            code: string;
+        };
+        returns: {
+            /** name of the new token, the device name with a number added if it was taken */
+            name: string;
+            /** token to send in the `secret` field */
+            token: string;
+        };
+    };
+};
+
+/**
+ * Library image slots. `grid` is the vertical capsule, `wideCapsule` the horizontal one shown in recent games, `hero` the banner on the game page and `logo` the title drawn over it.
+ */
+export type ArtworkKind = "grid" | "wideCapsule" | "hero" | "logo";
+
+/**
+ * Artwork file found for an app
+ */
+export interface Artwork {
+    kind: ArtworkKind;
+    path: string;
+    /** image width, missing if the file could not be read */
+    width?: number;
+    /** image height, missing if the file could not be read */
+    height?: number;
+}
+
+/**
+ * Fields that can be picked with the `fields` argument of `GetInstallFolders`
+ */
+export type InstallFolderField = "index" | "isDefault" | "isRemovable" | "isMounted" | "freeSpace" | "spaceUsedBySteam" | "totalCapacity" | "driveName" | "folderPath" | "userLabel";
+
+/**
+ * Library folder on this device. Fields filtered out with `fields` are missing.
+ */
+export interface InstallFolder {
+    /** index used by install commands */
+    index?: number;
+    /** is this the default folder? */
+    isDefault?: boolean;
+    /** is the folder on a removable drive? */
+    isRemovable?: boolean;
+    /** is the folder's drive mounted? */
+    isMounted?: boolean;
+    /** free space on the drive, in bytes */
+    freeSpace?: number;
+    /** space used by Steam, in bytes */
+    spaceUsedBySteam?: number;
+    /** total capacity of the drive, in bytes */
+    totalCapacity?: number;
+    /** drive name, a drive letter like "C:" on Windows */
+    driveName?: string;
+    /** path to the library folder */
+    folderPath?: string;
+    /** user label, empty if there's no label set */
+    userLabel?: string;
+}
@@ -281,6 +281,12 @@ import { type AppDetails, AppType } from "./steam";
                 appIds: appStore.allApps.map((app) => app.appid),
             };
         },
+        GetRunningApps: async () => {
+            return {
+                success: true,
+                appIds: SteamUIStore.RunningApps.map((app) => app.appid),
+            };
+        },
         SetUIMode: async (msg) => {
             if (!msg.args.mode) {
                 return {
@@ -470,6 +476,32 @@ import { type AppDetails, AppType } from "./steam";
     ws.addEventListener("message", async (event) => {
         if (event.data === "Ready") {
             window.rpcReady = true;
+            return;
+        }
+        if (event.data === "Shutdown") {
+            // Conductor is stopping, tear down like `window.terminate` without asking it to exit
+            console.log("Conductor is shutting down");
+            window.rpcReady = false;
+            window.rpc = undefined;
+            ws.close(1000, "Conductor is shutting down");
+            return;
+        }
+        if (event.data.startsWith("Notify:")) {
+            // Sent by Conductor itself, like the code for a pairing request
+            let notification: { title: string; body: string } = JSON.parse(
+                event.data.slice("Notify:".length),
+            );
+            console.log("Notification:", notification.title, notification.body);
+            try {
+                SteamClient.ClientNotifications.DisplayClientNotification(
+                    1,
+                    JSON.stringify(notification),
+                    () => {},
+                );
+            } catch (e) {
+                console.error("Failed to show notification:", e);
+            }
+            return;
         }
         let msg: {
             secret?: string | undefined;
@@ -397,6 +397,24 @@ declare global {
              */
             function GetUIMode(): Promise<UIMode>;
         }
+
+        /**
+         * Functions for Steam's notification toasts
+         */
+        namespace ClientNotifications {
+            /**
+             * Shows a notification toast in the corner of the screen, or at the top in Big Picture mode.
+             *
+             * @param type notification type, `1` is a general one with a title and body
+             * @param json notification contents as a JSON string
+             * @param callback called when the user clicks the notification
+             */
+            function DisplayClientNotification(
+                type: number,
+                json: string,
+                callback: (event: unknown) => void,
+            ): void;
+        }
     }
 
     /**
@@ -423,6 +441,16 @@ declare global {
         export function GetLocalizationForStoreTag(tagId: number): string;
     }
 
+    /**
+     * MobX store with the state of Steam's UI
+     */
+    namespace SteamUIStore {
+        /**
+         * Apps and shortcuts that are running right now
+         */
+        export let RunningApps: App[];
+    }
+
     /**
      * Stores info about your account and Steam's loading state
      */
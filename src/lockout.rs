@@ -0,0 +1,236 @@
+/*
+ *  lockout.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Slows down guessing tokens. Addresses that send too many wrong secrets, signatures or pairing
+//! codes are locked out for a while, and if failures pile up from many addresses every address
+//! is. Each lockout lasts twice as long as the last one, up to `max_lockout_secs`.
+
+use crate::config::LockoutConfig;
+use crate::tokens::AuthError;
+use chrono::{DateTime, TimeDelta, Utc};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+/// Failures and lockouts for one address, or for all of them
+#[derive(Default)]
+struct Counter {
+    failures: u32,
+    lockouts: u32,
+    locked_until: Option<DateTime<Utc>>,
+    last_failure: Option<DateTime<Utc>>,
+}
+
+impl Counter {
+    /// Seconds left until the lockout ends, if there is one
+    fn remaining(&self, now: DateTime<Utc>) -> Option<i64> {
+        self.locked_until
+            .filter(|until| *until > now)
+            .map(|until| (until - now).num_seconds().max(1))
+    }
+
+    /// Counts a failure. Returns how long the new lockout lasts if this one started it.
+    fn fail(
+        &mut self,
+        max_failures: u32,
+        limits: &LockoutConfig,
+        now: DateTime<Utc>,
+    ) -> Option<TimeDelta> {
+        if self.is_stale(limits, now) {
+            *self = Counter::default();
+        }
+        self.last_failure = Some(now);
+        self.failures += 1;
+        if max_failures == 0 || self.failures < max_failures {
+            return None;
+        }
+
+        let doublings = self.lockouts.min(31);
+        let secs = limits
+            .lockout_secs
+            .saturating_mul(1 << doublings)
+            .min(limits.max_lockout_secs);
+        let duration = TimeDelta::seconds(secs.try_into().unwrap_or(i64::MAX));
+        self.failures = 0;
+        self.lockouts += 1;
+        self.locked_until = Some(now + duration);
+        Some(duration)
+    }
+
+    /// Returns `true` if there were no failures for long enough to start over.
+    fn is_stale(&self, limits: &LockoutConfig, now: DateTime<Utc>) -> bool {
+        let forget_after =
+            TimeDelta::seconds(limits.max_lockout_secs.try_into().unwrap_or(i64::MAX));
+        self.remaining(now).is_none()
+            && self
+                .last_failure
+                .is_none_or(|last| now - last >= forget_after)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    addresses: HashMap<IpAddr, Counter>,
+    global: Counter,
+}
+
+/// Failed authentication counters for the server
+#[derive(Default)]
+pub struct Lockout(Mutex<State>);
+
+/// Returns `true` for failures that look like someone guessing a token.
+pub fn is_guess(error: &AuthError) -> bool {
+    matches!(error, AuthError::WrongSecret | AuthError::BadSignature)
+}
+
+fn is_exempt(limits: &LockoutConfig, ip: IpAddr) -> bool {
+    limits.exempt.iter().any(|net| net.contains(&ip))
+}
+
+impl Lockout {
+    /// Returns an error if `ip` is locked out right now.
+    pub fn check(
+        &self,
+        limits: &LockoutConfig,
+        ip: IpAddr,
+        now: DateTime<Utc>,
+    ) -> Result<(), AuthError> {
+        let ip = ip.to_canonical();
+        if is_exempt(limits, ip) {
+            return Ok(());
+        }
+
+        let state = self.0.lock().unwrap();
+        let remaining = state
+            .addresses
+            .get(&ip)
+            .and_then(|counter| counter.remaining(now))
+            .max(state.global.remaining(now));
+        match remaining {
+            Some(secs) => Err(AuthError::LockedOut(secs)),
+            None => Ok(()),
+        }
+    }
+
+    /// Counts a failed attempt from `ip` and locks it out if it had too many.
+    pub fn fail(&self, limits: &LockoutConfig, ip: IpAddr, now: DateTime<Utc>) {
+        let ip = ip.to_canonical();
+        if is_exempt(limits, ip) {
+            return;
+        }
+
+        let mut state = self.0.lock().unwrap();
+        state
+            .addresses
+            .retain(|_, counter| !counter.is_stale(limits, now));
+
+        let counter = state.addresses.entry(ip).or_default();
+        if let Some(duration) = counter.fail(limits.max_failures, limits, now) {
+            log::warn!(
+                "Locked out {ip} for {}s after {} failed attempts",
+                duration.num_seconds(),
+                limits.max_failures
+            );
+        }
+        if let Some(duration) = state.global.fail(limits.global_max_failures, limits, now) {
+            log::warn!(
+                "Locked out every address for {}s after {} failed attempts, exempt networks still work",
+                duration.num_seconds(),
+                limits.global_max_failures
+            );
+        }
+    }
+
+    /// Forgets the failures from `ip` after it sent a valid secret.
+    pub fn succeed(&self, ip: IpAddr) {
+        self.0.lock().unwrap().addresses.remove(&ip.to_canonical());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> LockoutConfig {
+        LockoutConfig {
+            max_failures: 3,
+            global_max_failures: 0,
+            lockout_secs: 10,
+            max_lockout_secs: 25,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn locks_out_for_longer_each_time() {
+        let lockout = Lockout::default();
+        let limits = limits();
+        let ip: IpAddr = "192.168.1.20".parse().unwrap();
+        let other: IpAddr = "192.168.1.21".parse().unwrap();
+        let mut now = Utc::now();
+
+        for expected in [10, 20, 25] {
+            for _ in 0..limits.max_failures {
+                assert_eq!(lockout.check(&limits, ip, now), Ok(()));
+                lockout.fail(&limits, ip, now);
+            }
+            assert_eq!(
+                lockout.check(&limits, ip, now),
+                Err(AuthError::LockedOut(expected))
+            );
+            assert_eq!(lockout.check(&limits, other, now), Ok(()));
+            now += TimeDelta::seconds(expected);
+        }
+
+        // A success or a long enough break starts over
+        lockout.fail(&limits, ip, now);
+        lockout.fail(&limits, ip, now);
+        lockout.succeed(ip);
+        lockout.fail(&limits, ip, now);
+        assert_eq!(lockout.check(&limits, ip, now), Ok(()));
+
+        now += TimeDelta::seconds(25);
+        for _ in 0..limits.max_failures {
+            lockout.fail(&limits, ip, now);
+        }
+        assert_eq!(
+            lockout.check(&limits, ip, now),
+            Err(AuthError::LockedOut(10))
+        );
+    }
+
+    #[test]
+    fn global_lockout_spares_exempt_networks() {
+        let lockout = Lockout::default();
+        let limits = LockoutConfig {
+            max_failures: 0,
+            global_max_failures: 4,
+            exempt: vec!["10.0.0.0/8".parse().unwrap()],
+            ..limits()
+        };
+        let now = Utc::now();
+
+        for i in 0..4 {
+            lockout.fail(&limits, IpAddr::from([192, 168, 1, i]), now);
+        }
+        assert_eq!(
+            lockout.check(&limits, "192.168.1.100".parse().unwrap(), now),
+            Err(AuthError::LockedOut(10))
+        );
+        assert_eq!(
+            lockout.check(&limits, "10.1.2.3".parse().unwrap(), now),
+            Ok(())
+        );
+        // IPv4 addresses can show up as IPv6 on dual stack sockets
+        assert_eq!(
+            lockout.check(&limits, "::ffff:10.1.2.3".parse().unwrap(), now),
+            Ok(())
+        );
+    }
+}
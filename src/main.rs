@@ -6,24 +6,52 @@
  *  License, v. 2.0. If a copy of the MPL was not distributed with this
  *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-use crate::config::Config;
-use crate::enable_cef::enable_cef_debugging;
+use crate::config::{Config, ConfigOverrides, env_log_level};
+use crate::daemon::Instance;
+use crate::enable_cef::{DisableOutcome, disable_cef_debugging, enable_cef_debugging};
+use crate::inject::DebuggerUrlError;
 use crate::secrets::generate_secret;
+use crate::steam::Steam;
 use clap::Parser;
 use log::LevelFilter;
 use std::fs::File;
 use std::io::{Read, Write};
-use std::str::FromStr;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
 use tokio::io::Error;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio::sync::watch;
 
+mod api_spec;
+mod artwork;
+mod cli;
 mod config;
+mod daemon;
 mod enable_cef;
 mod inject;
+mod lockout;
 mod message;
+mod pairing;
 mod payload;
+mod reload;
 mod secrets;
 mod server;
+mod settings_file;
+mod shortcuts;
+mod signing;
+mod steam;
+mod systemd;
+mod tokens;
+mod vdf;
+
+/// How many times to look for Steam's debugger before giving up, each try takes 1.5 seconds at
+/// most. Steam can take a while to load after a restart.
+const DEBUGGER_TRIES: u32 = 60;
+
+/// How long connections get to close before Conductor exits anyway
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Parser)]
 #[command(
@@ -32,21 +60,22 @@ mod server;
 )]
 struct Args {
     /// Path to the settings file
-    #[arg(short, long, default_value_t = String::from("./settings.toml"))]
+    #[arg(short, long, global = true, default_value_t = String::from("./settings.toml"))]
     settings_path: String,
+    #[command(flatten)]
+    overrides: ConfigOverrides,
+    #[command(subcommand)]
+    command: Option<cli::Command>,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    let settings_path = Args::parse().settings_path;
-
-    // Setup logger
-    let filter =
-        LevelFilter::from_str(&std::env::var("RUST_LOG").unwrap_or_else(|_| "info".to_owned()))
-            .unwrap_or(LevelFilter::Info);
+    let args = Args::parse();
+    let settings_path = args.settings_path;
 
+    // Setup logger, the level is set separately so it can change when the settings are reloaded
     env_logger::Builder::new()
-        .filter_level(filter)
+        .filter_level(LevelFilter::Trace)
         .format(|buf, record| {
             writeln!(
                 buf,
@@ -57,6 +86,13 @@ async fn main() -> Result<(), Error> {
             )
         })
         .init();
+    log::set_max_level(env_log_level());
+
+    let overrides = match args.command {
+        None => args.overrides,
+        Some(cli::Command::Start(start)) if !start.daemon => start.overrides,
+        Some(command) => std::process::exit(cli::run(command, &settings_path).await),
+    };
 
     let mut cfg = match Config::load(&settings_path) {
         Ok(c) => c,
@@ -65,127 +101,294 @@ async fn main() -> Result<(), Error> {
             std::process::exit(1);
         }
     };
+    cfg.apply(&overrides);
 
-    log::info!("Starting Conductor...");
-
-    if let Some(auth) = &mut cfg.auth {
-        if auth.enabled {
-            log::info!("Authentication enabled");
-
-            match &mut auth.tokens {
-                Some(vec) => {
-                    if vec.is_empty() {
-                        log::error!("No tokens found, add some or set `auth.enabled` to false");
-                        std::process::exit(1);
-                    }
-                }
-                None => {
-                    log::error!("No tokens found, add some or set `auth.enabled` to false");
-                    std::process::exit(1);
-                }
-            }
+    let errors = cfg.validate();
+    if !errors.is_empty() {
+        for e in &errors {
+            log::error!("{e}");
         }
+        log::error!("Fix the settings in {settings_path} and try again");
+        std::process::exit(1);
     }
+    log::set_max_level(cfg.conductor.log_level());
 
-    match enable_cef_debugging() {
-        Ok(_) => {}
+    let instance = match Instance::acquire() {
+        Ok(instance) => instance,
         Err(e) => {
-            log::error!("Failed to enable CEF debugging: {e}");
-            return Err(e);
+            log::error!("{e}");
+            std::process::exit(1);
         }
     };
 
     // Internal exit signal
     let (exit_tx, mut exit_rx) = unbounded_channel::<bool>();
 
-    // Get SteamWebHelper's debugger URL
-    let debugger_url = match inject::try_get_debugger_url(None).await {
-        Ok(url) => url,
+    if let Err(e) = instance
+        .listen(cfg.conductor.hostname.clone(), exit_tx.clone())
+        .await
+    {
+        log::error!("Failed to open control channel: {e}");
+        std::process::exit(1);
+    }
+
+    log::info!("Starting Conductor...");
+
+    if cfg.auth.as_ref().is_some_and(|auth| auth.enabled) {
+        log::info!("Authentication enabled");
+    }
+
+    let steam = match Steam::locate(cfg.conductor.steam_path.as_ref().map(Path::new)) {
+        Ok(steam) => steam,
         Err(e) => {
-            log::error!("Could not find debugger url: {e}");
+            log::error!("{e}");
             std::process::exit(1);
         }
     };
+    log::debug!(
+        "Using {:?} Steam install at {}",
+        steam.kind(),
+        steam.root().display()
+    );
+
+    let marker_created = match enable_cef_debugging(&steam) {
+        Ok(created) => created,
+        Err(e) => {
+            log::error!("Failed to enable CEF debugging: {e}");
+            return Err(e);
+        }
+    };
+
+    // Settings that can change while running, see `reload`
+    let (config_tx, config_rx) = watch::channel(cfg);
+    // Whether the payload is connected, systemd is told we're ready once it is
+    let connected = Arc::new(AtomicBool::new(false));
+    tokio::spawn(reload::watch(
+        settings_path.clone(),
+        overrides,
+        config_tx.clone(),
+        connected.clone(),
+    ));
+
+    // Tells the server to close its connections
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    let mut launch = Box::pin(launch(
+        config_tx,
+        settings_path,
+        steam.clone(),
+        marker_created,
+        exit_tx,
+        shutdown_rx,
+        connected,
+    ));
+    let mut launched = false;
+    let mut failed = false;
+
+    // Wait for exit event, this can come in while we're still waiting for Steam
+    loop {
+        tokio::select! {
+            result = &mut launch, if !launched => {
+                launched = true;
+                if let Err(e) = result {
+                    log::error!("{e}");
+                    failed = true;
+                    break;
+                }
+            }
+            _ = tokio::signal::ctrl_c() => break,
+            _ = terminate_signal() => break,
+            success = exit_rx.recv() => {
+                if success.is_some_and(|v| !v) {
+                    log::error!("^^^^^^^^ Exiting because of critical error above");
+                    failed = true;
+                }
+                break;
+            }
+        }
+    }
+
+    systemd::notify("STOPPING=1");
+
+    // Drop the launch future first so its receiver doesn't keep the shutdown waiting
+    drop(launch);
+    shut_down_server(shutdown_tx).await;
+
+    if config_rx.borrow().conductor.disable_cef_on_exit {
+        match disable_cef_debugging(&steam, false) {
+            Ok(DisableOutcome::Removed) => log::info!("Disabled CEF remote debugging"),
+            Ok(DisableOutcome::KeptUserFile) => {
+                log::info!("Leaving CEF remote debugging on, it was enabled outside Conductor")
+            }
+            Ok(DisableOutcome::NotEnabled) => {}
+            Err(e) => log::warn!("Failed to disable CEF remote debugging: {e}"),
+        }
+    }
+
+    if failed {
+        // `exit` skips destructors, so remove the pidfile first
+        drop(instance);
+        std::process::exit(1);
+    }
+    log::info!("Goodbye!");
+
+    Ok(())
+}
+
+/// Tells the server to stop accepting connections and close the open ones, then waits until
+/// they're closed or `SHUTDOWN_TIMEOUT` runs out.
+async fn shut_down_server(shutdown_tx: watch::Sender<bool>) {
+    if shutdown_tx.send(true).is_err() {
+        // The server never started
+        return;
+    }
+
+    log::info!("Closing connections...");
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, shutdown_tx.closed())
+        .await
+        .is_err()
+    {
+        log::warn!("Some connections did not close in time");
+    }
+}
+
+/// Starts Steam if needed, finds its debugger and spawns the server. Errors are returned so
+/// `main` can clean up before exiting.
+async fn launch(
+    config: watch::Sender<Config>,
+    settings_path: String,
+    steam: Steam,
+    marker_created: bool,
+    exit_tx: UnboundedSender<bool>,
+    shutdown: watch::Receiver<bool>,
+    connected: Arc<AtomicBool>,
+) -> Result<(), String> {
+    let cfg = config.borrow().clone();
+    let restart_allowed = cfg.conductor.restart_steam;
+    let mut restarted = false;
+
+    if !steam::is_running() {
+        if !restart_allowed {
+            return Err("Steam is not running, start it or set `restart_steam` to true".to_owned());
+        }
+        steam.launch().await.map_err(|e| e.to_string())?;
+        restarted = true;
+    } else if marker_created {
+        restart_steam(
+            &steam,
+            restart_allowed,
+            "Steam was started before remote debugging was enabled",
+        )
+        .await?;
+        restarted = true;
+    }
+
+    // Get SteamWebHelper's debugger URL
+    systemd::notify("STATUS=Waiting for Steam's debugger");
+    let mut debugger_url = inject::try_get_debugger_url(Some(DEBUGGER_TRIES)).await;
+
+    if let Err(DebuggerUrlError::MaxRetriesReached) = debugger_url
+        && !restarted
+    {
+        // The marker was there but Steam was started before it was created
+        restart_steam(&steam, restart_allowed, "Steam's debugger is not reachable").await?;
+        debugger_url = inject::try_get_debugger_url(Some(DEBUGGER_TRIES)).await;
+    }
+
+    let debugger_url = debugger_url.map_err(|e| format!("Could not find debugger url: {e}"))?;
 
     let steam_secret = generate_secret();
 
     if inject::is_another_instance_running(&debugger_url, &steam_secret).await {
         if cfg.conductor.replace_other_instances {
             log::debug!("Replacing other instances");
-            match inject::kill_running_instance(&debugger_url, 5).await {
-                Ok(_) => {}
-                Err(e) => {
-                    log::error!("Could not kill other instance: {e}");
-                    std::process::exit(1);
-                }
-            };
+            inject::kill_running_instance(&debugger_url, 5)
+                .await
+                .map_err(|e| format!("Could not kill other instance: {e}"))?;
         } else {
-            log::error!("Another instance is already running");
-            std::process::exit(1);
+            return Err("Another instance is already running".to_owned());
         }
     }
 
     tokio::time::sleep(std::time::Duration::from_millis(50)).await;
 
-    let (init_tx, init_rx) = unbounded_channel::<bool>();
-
     // Spawn server task
     tokio::spawn(start(
-        cfg,
+        config,
+        settings_path,
         exit_tx,
-        init_tx,
-        init_rx,
         debugger_url.clone(),
         steam_secret,
+        steam,
+        shutdown,
+        connected,
     ));
+    Ok(())
+}
 
-    // Wait for exit event
-    tokio::select! {
-        _ = tokio::signal::ctrl_c() => {},
-        success = exit_rx.recv() => {
-            if success.is_some_and(|v| !v) {
-                log::error!("^^^^^^^^ Exiting because of critical error above");
-                std::process::exit(1);
+/// Waits for SIGTERM, sent by service managers like systemd when stopping Conductor.
+async fn terminate_signal() {
+    cfg_if::cfg_if! {
+        if #[cfg(unix)] {
+            use tokio::signal::unix::{SignalKind, signal};
+
+            match signal(SignalKind::terminate()) {
+                Ok(mut sigterm) => _ = sigterm.recv().await,
+                Err(_) => std::future::pending().await,
             }
+        } else {
+            std::future::pending().await
         }
     }
+}
 
-    log::info!("Goodbye!");
+/// Restarts Steam so it opens the debugger port, or fails explaining `reason` if
+/// `restart_steam` is turned off.
+async fn restart_steam(steam: &Steam, allowed: bool, reason: &str) -> Result<(), String> {
+    if !allowed {
+        return Err(format!(
+            "{reason}, restart Steam or set `restart_steam` to true"
+        ));
+    }
 
-    Ok(())
+    log::warn!("{reason}, restarting Steam");
+    steam.restart().await.map_err(|e| e.to_string())
 }
 
+#[allow(clippy::too_many_arguments)] // passed through to `server::serve`
 async fn start(
-    cfg: Config,
+    config: watch::Sender<Config>,
+    settings_path: String,
     exit_tx: UnboundedSender<bool>,
-    init_tx: UnboundedSender<bool>,
-    init_rx: UnboundedReceiver<bool>,
     debugger_url: String,
     steam_secret: String,
+    steam: Steam,
+    shutdown: watch::Receiver<bool>,
+    connected: Arc<AtomicBool>,
 ) {
     tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-    let Ok(current_exe) = std::env::current_exe() else {
-        log::error!("Could not get current executable");
-        _ = exit_tx.send(false);
-        return;
-    };
+    let (init_tx, init_rx) = unbounded_channel::<bool>();
+    let cfg = config.borrow().clone();
 
-    // Find and open payload file
-    let Some(current_dir) = current_exe.parent() else {
-        log::error!("Could not get current directory");
-        _ = exit_tx.send(false);
-        return;
+    // Find and read payload file
+    let payload_file = match cfg.conductor.payload_file() {
+        Ok(path) => path,
+        Err(e) => {
+            log::error!("Could not find payload file: {e}");
+            _ = exit_tx.send(false);
+            return;
+        }
     };
 
-    let Ok(mut js_file) = File::open(current_dir.join(cfg.conductor.payload_path)) else {
-        log::error!("Could not open payload file at {}", current_dir.display());
+    let Ok(mut js_file) = File::open(&payload_file) else {
+        log::error!("Could not open payload file at {}", payload_file.display());
         _ = exit_tx.send(false);
         return;
     };
 
     let mut payload = String::with_capacity(10_000);
     let Ok(_) = js_file.read_to_string(&mut payload) else {
-        log::error!("Could not read payload file at {}", current_dir.display());
+        log::error!("Could not read payload file at {}", payload_file.display());
         _ = exit_tx.send(false);
         return;
     };
@@ -204,10 +407,14 @@ async fn start(
     tokio::spawn(server::serve(
         cfg.conductor.hostname,
         steam_secret,
-        cfg.auth,
+        config,
+        settings_path,
         payload.clone(),
+        steam,
         exit_tx.clone(),
         init_tx,
+        shutdown,
+        connected,
     ));
 
     let new_tx = exit_tx.clone();
@@ -223,12 +430,8 @@ async fn start(
     });
 
     // Inject payload into SteamWebHelper
-    match inject::inject_payload(&debugger_url, &payload, 5).await {
-        Ok(()) => {}
-        Err(err) => {
-            log::error!("{err}");
-            _ = exit_tx.send(false);
-            return;
-        }
+    if let Err(err) = inject::inject_payload(&debugger_url, &payload, 5).await {
+        log::error!("{err}");
+        _ = exit_tx.send(false);
     }
 }
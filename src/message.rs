@@ -7,6 +7,7 @@
  *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
+use conductor_client::signing::Signature;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -16,6 +17,8 @@ pub struct RpcRequest<'a> {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub secret: Option<&'a str>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<Signature>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub message_id: Option<u32>,
     pub args: serde_json::Value,
 }
@@ -0,0 +1,410 @@
+/*
+ *  pairing.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Pairing gives new devices a token without editing `settings.toml`. A device asks with
+//! `RequestPairing`, Conductor shows a short code on the Steam machine and the device sends it
+//! back with `ConfirmPairing`. Typing the code in proves the user can see the screen, so the
+//! device gets a token saved like one from `conductor token add`.
+//!
+//! Anyone can ask to pair, so each address can only ask every `REQUEST_INTERVAL` seconds and too
+//! many wrong codes from all devices together pause pairing for a while.
+
+use crate::config::AuthConfig;
+use crate::secrets::generate_secret;
+use chrono::{DateTime, TimeDelta, Utc};
+use rand::Rng;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+
+/// Seconds a pairing code can be used for
+const CODE_TTL: i64 = 120;
+
+/// Pairing requests waiting for their code at the same time
+const MAX_PENDING: usize = 3;
+
+/// Wrong codes allowed before a request is dropped
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Seconds an address has to wait between pairing requests
+const REQUEST_INTERVAL: i64 = 30;
+
+/// Wrong codes for all requests together before pairing is paused
+const MAX_WRONG_CODES: u32 = 10;
+
+/// Seconds pairing stays paused after too many wrong codes. Wrong codes older than this are
+/// forgotten.
+const PAUSE_SECS: i64 = 600;
+
+const MAX_NAME_LEN: usize = 64;
+
+/// Commands that work without a token while pairing is on
+pub const COMMANDS: [&str; 2] = ["RequestPairing", "ConfirmPairing"];
+
+#[derive(Error, Debug, PartialEq)]
+pub enum PairingError {
+    #[error("Pairing is turned off, set `pairing = true` under [auth] to use it")]
+    Disabled,
+    #[error("Device names must be 1 to {MAX_NAME_LEN} characters")]
+    InvalidName,
+    #[error("Unknown command {0} in scopes")]
+    UnknownScope(String),
+    #[error("Too many devices are pairing right now, try again in a few minutes")]
+    TooManyRequests,
+    #[error("Wait {0} seconds before asking to pair again")]
+    TooSoon(i64),
+    #[error("Pairing is paused for {0} seconds after too many wrong codes")]
+    Paused(i64),
+    #[error("No pairing request with this ID, it may have expired")]
+    NotFound,
+    #[error("Wrong pairing code")]
+    WrongCode,
+}
+
+/// A device that asked to pair
+#[derive(Debug, PartialEq)]
+pub struct Device {
+    pub name: String,
+    /// Commands the device's token can run, all of them if not set
+    pub scopes: Option<Vec<String>>,
+}
+
+struct Pending {
+    device: Device,
+    code: String,
+    expires_at: DateTime<Utc>,
+    attempts: u32,
+}
+
+/// A new pairing request. `id` goes back to the device, `code` is only shown on the Steam machine.
+pub struct Request {
+    pub id: String,
+    pub code: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Requests waiting for their code, by ID
+    pending: HashMap<String, Pending>,
+    /// When each address last asked to pair
+    last_requests: HashMap<IpAddr, DateTime<Utc>>,
+    wrong_codes: u32,
+    last_wrong_code: Option<DateTime<Utc>>,
+    paused_until: Option<DateTime<Utc>>,
+}
+
+impl State {
+    /// Drops expired requests and returns an error if pairing is paused.
+    fn clean_up(&mut self, now: DateTime<Utc>) -> Result<(), PairingError> {
+        self.pending.retain(|_, pairing| pairing.expires_at > now);
+        self.last_requests
+            .retain(|_, last| now - *last < TimeDelta::seconds(REQUEST_INTERVAL));
+        if self
+            .last_wrong_code
+            .is_some_and(|last| now - last >= TimeDelta::seconds(PAUSE_SECS))
+        {
+            self.wrong_codes = 0;
+        }
+
+        match self.paused_until {
+            Some(until) if until > now => {
+                Err(PairingError::Paused((until - now).num_seconds().max(1)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Counts a wrong code and pauses pairing if there were too many.
+    fn wrong_code(&mut self, now: DateTime<Utc>) {
+        self.wrong_codes += 1;
+        self.last_wrong_code = Some(now);
+        if self.wrong_codes < MAX_WRONG_CODES {
+            return;
+        }
+
+        log::warn!(
+            "Paused pairing for {PAUSE_SECS}s after {MAX_WRONG_CODES} wrong codes, waiting \
+             requests were cancelled"
+        );
+        self.wrong_codes = 0;
+        self.pending.clear();
+        self.paused_until = Some(now + TimeDelta::seconds(PAUSE_SECS));
+    }
+}
+
+/// Pairing requests waiting for their code, and the limits on making new ones
+#[derive(Default)]
+pub struct Pairings(Mutex<State>);
+
+impl Pairings {
+    /// Starts pairing a device for `ip` and makes its code.
+    pub fn request(
+        &self,
+        device: Device,
+        ip: IpAddr,
+        now: DateTime<Utc>,
+    ) -> Result<Request, PairingError> {
+        if device.name.trim().is_empty() || device.name.chars().count() > MAX_NAME_LEN {
+            return Err(PairingError::InvalidName);
+        }
+        let schema = conductor_schema::schema();
+        if let Some(scope) = device
+            .scopes
+            .iter()
+            .flatten()
+            .find(|scope| schema.command(scope).is_none())
+        {
+            return Err(PairingError::UnknownScope(scope.clone()));
+        }
+
+        let ip = ip.to_canonical();
+        let mut state = self.0.lock().unwrap();
+        state.clean_up(now)?;
+        if let Some(last) = state.last_requests.get(&ip) {
+            let wait = TimeDelta::seconds(REQUEST_INTERVAL) - (now - *last);
+            return Err(PairingError::TooSoon(wait.num_seconds().max(1)));
+        }
+        if state.pending.len() >= MAX_PENDING {
+            return Err(PairingError::TooManyRequests);
+        }
+
+        let request = Request {
+            id: generate_secret(),
+            code: format!("{:06}", rand::rng().random_range(0..1_000_000)),
+            expires_at: now + TimeDelta::seconds(CODE_TTL),
+        };
+        state.last_requests.insert(ip, now);
+        state.pending.insert(
+            request.id.clone(),
+            Pending {
+                device,
+                code: request.code.clone(),
+                expires_at: request.expires_at,
+                attempts: 0,
+            },
+        );
+        Ok(request)
+    }
+
+    /// Checks the code for a pairing request and returns the device if it's right. Each request
+    /// can only be confirmed once, and is dropped after too many wrong codes.
+    pub fn confirm(
+        &self,
+        id: &str,
+        code: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Device, PairingError> {
+        let mut state = self.0.lock().unwrap();
+        state.clean_up(now)?;
+        let pairing = state.pending.get_mut(id).ok_or(PairingError::NotFound)?;
+
+        if bool::from(pairing.code.as_bytes().ct_eq(code.as_bytes())) {
+            let pairing = state.pending.remove(id).expect("found above");
+            return Ok(pairing.device);
+        }
+
+        pairing.attempts += 1;
+        if pairing.attempts >= MAX_ATTEMPTS {
+            state.pending.remove(id);
+        }
+        state.wrong_code(now);
+        Err(PairingError::WrongCode)
+    }
+}
+
+/// Picks a token name for a device that isn't used by another token or key.
+pub fn token_name(auth: Option<&AuthConfig>, device: &str) -> String {
+    let taken = |name: &str| {
+        auth.is_some_and(|auth| {
+            auth.hashed_tokens.iter().any(|token| token.name == name)
+                || auth.signing_keys.iter().any(|key| key.name == name)
+        })
+    };
+
+    let device = device.trim();
+    if !taken(device) {
+        return device.to_owned();
+    }
+    (2..)
+        .map(|i| format!("{device}-{i}"))
+        .find(|name| !taken(name))
+        .expect("some name is free")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokens::HashedToken;
+
+    fn device(name: &str) -> Device {
+        Device {
+            name: name.to_owned(),
+            scopes: None,
+        }
+    }
+
+    fn ip(i: u8) -> IpAddr {
+        IpAddr::from([192, 168, 1, i])
+    }
+
+    fn wrong_code(code: &str) -> &'static str {
+        if code == "000000" { "000001" } else { "000000" }
+    }
+
+    #[test]
+    fn confirms_with_the_right_code() {
+        let pairings = Pairings::default();
+        let now = Utc::now();
+
+        let request = pairings.request(device("phone"), ip(1), now).unwrap();
+        assert_eq!(request.code.len(), 6);
+        assert_eq!(
+            pairings.confirm("unknown", &request.code, now),
+            Err(PairingError::NotFound)
+        );
+        assert_eq!(
+            pairings.confirm(&request.id, &request.code, now),
+            Ok(device("phone"))
+        );
+        assert_eq!(
+            pairings.confirm(&request.id, &request.code, now),
+            Err(PairingError::NotFound)
+        );
+
+        let request = pairings.request(device("tv"), ip(2), now).unwrap();
+        let later = request.expires_at;
+        assert_eq!(
+            pairings.confirm(&request.id, &request.code, later),
+            Err(PairingError::NotFound)
+        );
+    }
+
+    #[test]
+    fn limits_guesses_and_requests() {
+        let pairings = Pairings::default();
+        let now = Utc::now();
+
+        let request = pairings.request(device("phone"), ip(1), now).unwrap();
+        let wrong = wrong_code(&request.code);
+        for _ in 0..MAX_ATTEMPTS {
+            assert_eq!(
+                pairings.confirm(&request.id, wrong, now),
+                Err(PairingError::WrongCode)
+            );
+        }
+        assert_eq!(
+            pairings.confirm(&request.id, &request.code, now),
+            Err(PairingError::NotFound)
+        );
+
+        for i in 0..MAX_PENDING {
+            pairings
+                .request(device("tv"), ip(10 + i as u8), now)
+                .unwrap();
+        }
+        assert!(matches!(
+            pairings.request(device("tv"), ip(20), now),
+            Err(PairingError::TooManyRequests)
+        ));
+
+        let later = now + TimeDelta::seconds(CODE_TTL);
+        assert!(pairings.request(device("tv"), ip(20), later).is_ok());
+    }
+
+    #[test]
+    fn limits_requests_per_address() {
+        let pairings = Pairings::default();
+        let now = Utc::now();
+
+        pairings.request(device("phone"), ip(1), now).unwrap();
+        assert!(matches!(
+            pairings.request(device("phone"), ip(1), now + TimeDelta::seconds(10)),
+            Err(PairingError::TooSoon(20))
+        ));
+        // IPv4 addresses can show up as IPv6 on dual stack sockets
+        assert!(matches!(
+            pairings.request(device("phone"), "::ffff:192.168.1.1".parse().unwrap(), now),
+            Err(PairingError::TooSoon(_))
+        ));
+        assert!(pairings.request(device("tv"), ip(2), now).is_ok());
+
+        let later = now + TimeDelta::seconds(REQUEST_INTERVAL);
+        assert!(pairings.request(device("phone"), ip(1), later).is_ok());
+    }
+
+    #[test]
+    fn pauses_after_too_many_wrong_codes() {
+        let pairings = Pairings::default();
+        let mut now = Utc::now();
+        let mut wrong_codes = 0;
+
+        // Each address asks once and guesses until its request is dropped
+        let mut address = 0;
+        let paused = 'cycles: loop {
+            address += 1;
+            let request = match pairings.request(device("tv"), ip(address), now) {
+                Ok(request) => request,
+                Err(e) => break e,
+            };
+            for _ in 0..MAX_ATTEMPTS {
+                match pairings.confirm(&request.id, wrong_code(&request.code), now) {
+                    Err(PairingError::WrongCode) => wrong_codes += 1,
+                    Err(e) => break 'cycles e,
+                    Ok(_) => panic!("guessed the code"),
+                }
+            }
+            now += TimeDelta::seconds(1);
+        };
+
+        assert_eq!(wrong_codes, MAX_WRONG_CODES);
+        assert!(matches!(paused, PairingError::Paused(_)));
+        assert!(matches!(
+            pairings.confirm("any", "000000", now),
+            Err(PairingError::Paused(_))
+        ));
+
+        now += TimeDelta::seconds(PAUSE_SECS);
+        assert!(pairings.request(device("tv"), ip(100), now).is_ok());
+    }
+
+    #[test]
+    fn checks_devices() {
+        let pairings = Pairings::default();
+        let now = Utc::now();
+
+        assert!(matches!(
+            pairings.request(device(" "), ip(1), now),
+            Err(PairingError::InvalidName)
+        ));
+        let scoped = Device {
+            name: "tv".to_owned(),
+            scopes: Some(vec!["RunApp".to_owned(), "Format".to_owned()]),
+        };
+        assert!(matches!(
+            pairings.request(scoped, ip(1), now),
+            Err(PairingError::UnknownScope(scope)) if scope == "Format"
+        ));
+    }
+
+    #[test]
+    fn names_tokens_uniquely() {
+        let (token, _) = HashedToken::generate("tv".to_owned(), None, None, None);
+        let auth = AuthConfig {
+            hashed_tokens: vec![token],
+            ..Default::default()
+        };
+
+        assert_eq!(token_name(None, "tv"), "tv");
+        assert_eq!(token_name(Some(&auth), "phone"), "phone");
+        assert_eq!(token_name(Some(&auth), "tv"), "tv-2");
+    }
+}
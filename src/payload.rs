@@ -12,14 +12,11 @@ use regex::{Captures, Regex};
 pub fn make_payload(template: &str, hostname: &str, replace: bool, secret: String) -> String {
     let re = Regex::new(r"(\$REPLACE)|(\$SECRET)|(\$HOSTNAME)").unwrap();
 
-    let payload = re
-        .replace_all(template, |caps: &Captures| match &caps[0] {
-            "$REPLACE" => replace.to_string(),
-            "$SECRET" => secret.to_string(),
-            "$HOSTNAME" => hostname.to_string(),
-            _ => unreachable!(),
-        })
-        .to_string();
-
-    payload
+    re.replace_all(template, |caps: &Captures| match &caps[0] {
+        "$REPLACE" => replace.to_string(),
+        "$SECRET" => secret.to_string(),
+        "$HOSTNAME" => hostname.to_string(),
+        _ => unreachable!(),
+    })
+    .to_string()
 }
@@ -0,0 +1,134 @@
+/*
+ *  reload.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Reloads `settings.toml` while the server is running, when the file changes or on `SIGHUP`.
+//! See [`Config::reload`] for which settings apply right away.
+
+use crate::config::{Config, ConfigOverrides};
+use crate::systemd;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+
+/// How often to check if the settings file changed
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Watches the settings file and publishes the new settings to `config` until every receiver is
+/// gone. systemd is only told about reloads while `connected` is set, before that it hasn't been
+/// told Conductor is ready yet.
+pub async fn watch(
+    path: String,
+    overrides: ConfigOverrides,
+    config: watch::Sender<Config>,
+    connected: Arc<AtomicBool>,
+) {
+    let mut modified = modified_time(&path);
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    let mut hangup = hangup_listener();
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let current = modified_time(&path);
+                if current == modified {
+                    continue;
+                }
+                modified = current;
+                log::info!("{path} changed, reloading settings");
+            }
+            _ = wait_for_hangup(&mut hangup) => log::info!("Received SIGHUP, reloading settings"),
+            _ = config.closed() => return,
+        }
+
+        let ready = connected.load(Ordering::Relaxed);
+        if ready {
+            systemd::notify("RELOADING=1");
+        }
+        reload(&path, &overrides, &config);
+        if ready {
+            systemd::notify("READY=1");
+        }
+    }
+}
+
+/// Loads and checks the settings file, then applies what can be changed live. Invalid settings
+/// are logged and the current ones are kept.
+fn reload(path: &str, overrides: &ConfigOverrides, config: &watch::Sender<Config>) {
+    let mut new = match Config::load(path) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            log::error!("Failed to reload settings from {path}: {e}");
+            return;
+        }
+    };
+    new.apply(overrides);
+
+    let errors = new.validate();
+    if !errors.is_empty() {
+        for e in &errors {
+            log::error!("{e}");
+        }
+        log::error!("Keeping the current settings, fix {path} to apply changes");
+        return;
+    }
+
+    let mut updated = config.borrow().clone();
+    for name in updated.reload(new) {
+        log::warn!("`{name}` changed, restart Conductor to apply it");
+    }
+
+    let changed = config.send_if_modified(|current| {
+        if *current == updated {
+            return false;
+        }
+        *current = updated;
+        true
+    });
+
+    if changed {
+        log::set_max_level(config.borrow().conductor.log_level());
+        log::info!("Applied new settings");
+    } else {
+        log::debug!("No settings that can be changed live were changed");
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(unix)] {
+        use tokio::signal::unix::{Signal, SignalKind, signal};
+
+        fn hangup_listener() -> Option<Signal> {
+            match signal(SignalKind::hangup()) {
+                Ok(signal) => Some(signal),
+                Err(e) => {
+                    log::warn!("Can't listen for SIGHUP, only file changes reload settings: {e}");
+                    None
+                }
+            }
+        }
+
+        async fn wait_for_hangup(hangup: &mut Option<Signal>) {
+            match hangup {
+                Some(hangup) => _ = hangup.recv().await,
+                None => std::future::pending().await,
+            }
+        }
+    } else {
+        fn hangup_listener() {}
+
+        async fn wait_for_hangup(_: &mut ()) {
+            std::future::pending().await
+        }
+    }
+}
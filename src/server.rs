@@ -7,81 +7,226 @@
  *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 
-use crate::config::AuthConfig;
+use crate::api_spec;
+use crate::artwork;
+use crate::config::Config;
 use crate::inject::{inject_payload, try_get_debugger_url};
+use crate::lockout::{self, Lockout};
 use crate::message::RpcRequest;
-use futures_util::{SinkExt, StreamExt};
+use crate::pairing::{self, Device, PairingError, Pairings};
+use crate::settings_file;
+use crate::signing::{self, NonceCache};
+use crate::steam::Steam;
+use crate::systemd;
+use crate::tokens::{self, AuthError, Credential, HashedToken, Sessions};
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use futures_util::{FutureExt, SinkExt, StreamExt};
 use serde_json::json;
 use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::time::Duration;
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::RwLock;
 use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio::sync::{RwLock, watch};
 use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::http::header::ORIGIN;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
 use tokio_tungstenite::tungstenite::{Message, Utf8Bytes};
 
+/// Error sent for requests that are still waiting when the server stops, also used as the
+/// close reason
+const SHUTDOWN_REASON: &str = "Conductor is shutting down";
+
 struct Context {
-    connected: AtomicBool,
+    /// Set once the payload sent `init:`, shared with `reload` so systemd only hears about
+    /// reloads while Conductor is ready
+    connected: Arc<AtomicBool>,
     steam_tx: RwLock<Option<UnboundedSender<String>>>,
     last_message_id: AtomicU32,
     message_senders: RwLock<HashMap<u32, UnboundedSender<String>>>,
     message_ids: RwLock<HashMap<u32, u32>>,
     steam_secret: String,
-    auth_enabled: bool,
-    client_secrets: Option<Vec<String>>,
+    /// Current settings, auth changes when `settings.toml` is reloaded or a device is paired
+    config: watch::Sender<Config>,
+    settings_path: String,
+    sessions: Sessions,
+    /// Nonces of signed requests, so they can't be replayed
+    nonces: NonceCache,
+    pairings: Pairings,
+    /// Failed attempts by address, see `[auth.lockout]`
+    lockout: Lockout,
     payload: String,
+    steam: Steam,
     exit_tx: UnboundedSender<bool>,
     init_tx: UnboundedSender<bool>,
+    shutdown: watch::Receiver<bool>,
 }
 
+impl Context {
+    /// Checks a client request from `ip`, unless that address is locked out after too many
+    /// failed attempts or is in `skip_auth`.
+    fn authorize(&self, req: &RpcRequest, ip: IpAddr) -> Result<Credential, AuthError> {
+        let config = self.config.borrow();
+        if config.network.skips_auth(ip) {
+            return Ok(Credential::Anonymous);
+        }
+
+        let now = Utc::now();
+        let Some(limits) = config.auth.as_ref().map(|auth| &auth.lockout) else {
+            return self.check_credentials(&config, req, now);
+        };
+
+        self.lockout.check(limits, ip, now)?;
+        let result = self.check_credentials(&config, req, now);
+        match &result {
+            Ok(Credential::Anonymous) => {}
+            Ok(_) => self.lockout.succeed(ip),
+            Err(e) if lockout::is_guess(e) => self.lockout.fail(limits, ip, now),
+            Err(_) => {}
+        }
+        result
+    }
+
+    /// Checks the signature or secret of a client request against the current keys and tokens,
+    /// so revoked ones stop working as soon as the settings are reloaded.
+    fn check_credentials(
+        &self,
+        config: &Config,
+        req: &RpcRequest,
+        now: DateTime<Utc>,
+    ) -> Result<Credential, AuthError> {
+        let pairing = config.auth.as_ref().is_some_and(|auth| auth.pairing);
+        if pairing && pairing::COMMANDS.contains(&req.command) {
+            // New devices don't have a token yet
+            return Ok(Credential::Anonymous);
+        }
+        if let Some(signature) = &req.signature {
+            return signing::authorize(
+                config.auth.as_ref(),
+                &self.nonces,
+                signature,
+                req.command,
+                &req.args,
+                now,
+            );
+        }
+        tokens::authorize(
+            config.auth.as_ref(),
+            &self.sessions,
+            req.secret,
+            req.command,
+            now,
+        )
+    }
+
+    fn pairing_enabled(&self) -> bool {
+        self.config
+            .borrow()
+            .auth
+            .as_ref()
+            .is_some_and(|auth| auth.pairing)
+    }
+}
+
+#[allow(clippy::too_many_arguments)] // one for each part of the shared context
 pub async fn serve(
     addr: String,
     steam_secret: String,
-    auth_cfg: Option<AuthConfig>,
+    config: watch::Sender<Config>,
+    settings_path: String,
     payload: String,
+    steam: Steam,
     exit_tx: UnboundedSender<bool>,
     init_tx: UnboundedSender<bool>,
+    shutdown: watch::Receiver<bool>,
+    connected: Arc<AtomicBool>,
 ) {
     // Create the event loop and TCP listener we'll accept connections on
     let try_socket = TcpListener::bind(&addr).await;
     let listener = try_socket.expect("Failed to bind");
     log::info!("Listening on {}", addr);
 
-    let mut auth_enabled = false;
-
-    if let Some(cfg) = &auth_cfg {
-        if cfg.enabled {
-            auth_enabled = true;
-        }
-    }
-
     let ctx = Arc::new(Context {
-        connected: false.into(),
+        connected,
         steam_tx: None.into(),
         last_message_id: 0.into(),
         message_senders: HashMap::new().into(),
         message_ids: HashMap::new().into(),
         steam_secret,
-        auth_enabled,
-        client_secrets: auth_cfg.and_then(|cfg| cfg.tokens),
+        config,
+        settings_path,
+        sessions: Sessions::default(),
+        nonces: NonceCache::default(),
+        pairings: Pairings::default(),
+        lockout: Lockout::default(),
         payload,
+        steam,
         exit_tx,
         init_tx,
+        shutdown,
     });
 
-    while let Ok((stream, _)) = listener.accept().await {
-        tokio::spawn(handle_connection(ctx.clone(), stream));
+    let mut shutdown = ctx.shutdown.clone();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let Ok((stream, _)) = accepted else {
+                    break;
+                };
+                tokio::spawn(handle_connection(ctx.clone(), stream));
+            }
+            _ = shutdown.changed() => {
+                log::debug!("Stopped accepting connections");
+                break;
+            }
+        }
     }
 }
-async fn handle_connection(ctx: Arc<Context>, stream: TcpStream) {
+
+async fn handle_connection(ctx: Arc<Context>, mut stream: TcpStream) {
     let addr = stream
         .peer_addr()
         .expect("connected streams should have a peer address");
     log::debug!("Peer address: {}", addr);
 
-    let mut ws_stream = match tokio_tungstenite::accept_async(stream).await {
+    if !ctx.config.borrow().network.allows(addr.ip()) {
+        log::warn!("Refused connection from {addr}, it's not allowed by [network]");
+        return;
+    }
+
+    if api_spec::try_serve(&mut stream).await {
+        return;
+    }
+
+    // Browsers send the page's origin, apps and scripts usually don't send one at all
+    #[allow(clippy::result_large_err)] // tungstenite's callback returns the whole response
+    let check_origin = |req: &Request, res: Response| {
+        let Some(origin) = req.headers().get(ORIGIN) else {
+            return Ok(res);
+        };
+        let allowed = origin
+            .to_str()
+            .is_ok_and(|origin| ctx.config.borrow().network.allows_origin(origin));
+        if allowed {
+            return Ok(res);
+        }
+
+        log::warn!(
+            "Refused connection from {addr} with origin {origin:?}, add it to `allowed_origins` \
+             under [network] to allow it"
+        );
+        let mut res = ErrorResponse::new(Some("Origin not allowed".to_owned()));
+        *res.status_mut() = StatusCode::FORBIDDEN;
+        Err(res)
+    };
+
+    let mut ws_stream = match tokio_tungstenite::accept_hdr_async(stream, check_origin).await {
         Ok(ws) => ws,
         Err(e) => {
             log::error!("WebSocket handshake failed: {}", e);
@@ -93,9 +238,17 @@ async fn handle_connection(ctx: Arc<Context>, stream: TcpStream) {
 
     let (tx, mut rx) = unbounded_channel::<String>();
     let mut is_steam = false;
+    let mut shutdown = ctx.shutdown.clone();
 
     // Handle initial message
-    let Some(initial_msg) = ws_stream.next().await else {
+    let initial_msg = tokio::select! {
+        msg = ws_stream.next() => msg,
+        _ = shutdown.changed() => {
+            close_connection(&ctx, &mut ws_stream, &tx, false).await;
+            return;
+        }
+    };
+    let Some(initial_msg) = initial_msg else {
         log::error!("Peer connected without sending initial message");
         return;
     };
@@ -122,6 +275,7 @@ async fn handle_connection(ctx: Arc<Context>, stream: TcpStream) {
         ctx.connected.store(true, Ordering::Relaxed);
 
         _ = ctx.init_tx.send(true);
+        systemd::notify("READY=1\nSTATUS=Connected to Steam");
 
         is_steam = true;
 
@@ -136,87 +290,21 @@ async fn handle_connection(ctx: Arc<Context>, stream: TcpStream) {
         drop(steam_tx);
     } else {
         // Client connection
-        let steam_tx = ctx.steam_tx.read().await;
-        if let Some(steam_tx) = steam_tx.as_ref() {
-            if let Ok(mut req) = serde_json::from_str::<RpcRequest>(&msg_text) {
-                if ctx.auth_enabled {
-                    if req.secret.is_none() {
-                        log::warn!("Received message without secret: {}", msg_text);
-                        send_message(
-                            &mut ws_stream,
-                            &json!({
-                                "success": false,
-                                "error": "A secret is required",
-                            }),
-                        )
-                        .await;
-                        return;
-                    }
-
-                    if ctx
-                        .client_secrets
-                        .as_ref()
-                        .unwrap()
-                        .iter()
-                        .all(|secret| secret != req.secret.unwrap())
-                    {
-                        log::warn!("Received message with wrong secret: {}", msg_text);
-                        send_message(
-                            &mut ws_stream,
-                            &json!({
-                                "success": false,
-                                "error": "Wrong secret! Are you a hacker?",
-                            }),
-                        )
-                        .await;
-                        return;
-                    }
-                }
-
-                req.secret = Some(&ctx.steam_secret);
-
-                let message_id = ctx.last_message_id.fetch_add(1, Ordering::Relaxed);
-
-                if let Some(id) = req.message_id {
-                    log::debug!("Inserting new id {}:{}", id, message_id);
-                    ctx.message_ids.write().await.insert(message_id, id);
-                }
-
-                req.message_id = Some(message_id);
-
-                ctx.message_senders
-                    .write()
-                    .await
-                    .insert(message_id, tx.clone());
-
-                let req = serde_json::to_string(&req).expect("failed to serialize message");
-                if let Err(e) = steam_tx.send(req) {
-                    log::error!("Error sending message to Steam: {}", e);
-                    return;
-                }
-            } else {
-                log::warn!("Received invalid message: {}", msg_text);
-                send_message(
-                    &mut ws_stream,
-                    &json!({
-                        "success": false,
-                        "error": "Message is not valid",
-                    }),
-                )
-                .await;
-                return;
-            }
-        } else {
-            log::warn!("Received command before connecting to Steam");
+        let Ok(req) = serde_json::from_str::<RpcRequest>(&msg_text) else {
+            log::warn!("Received invalid message: {}", msg_text);
             send_message(
                 &mut ws_stream,
                 &json!({
                     "success": false,
-                    "error": "Not connected to Steam",
+                    "error": "Message is not valid",
                 }),
             )
             .await;
             return;
+        };
+
+        if !handle_client_message(ctx.clone(), req, addr, &mut ws_stream, &tx).await {
+            return;
         }
     }
 
@@ -239,7 +327,13 @@ async fn handle_connection(ctx: Arc<Context>, stream: TcpStream) {
                             handle_steam_message(ctx.clone(), &msg_text).await;
                         } else {
                             log::debug!("Received client message: '{}'", msg_text);
-                            handle_client_message(ctx.clone(), &msg_text, &mut ws_stream, &mut tx.clone()).await;
+                            let Ok(req) = serde_json::from_str::<RpcRequest>(&msg_text) else {
+                                log::error!("Failed to deserialize client message: {msg_text}");
+                                continue;
+                            };
+                            if !handle_client_message(ctx.clone(), req, addr, &mut ws_stream, &tx).await {
+                                break;
+                            }
                         }
                     },
                     Some(Err(e)) => {
@@ -267,12 +361,20 @@ async fn handle_connection(ctx: Arc<Context>, stream: TcpStream) {
                     }
                 }
             }
+            _ = shutdown.changed() => {
+                close_connection(&ctx, &mut ws_stream, &tx, is_steam).await;
+                break;
+            }
         }
     }
 
     // Cleanup
-    if is_steam {
+    if is_steam && *shutdown.borrow() {
+        ctx.connected.store(false, Ordering::Relaxed);
+        ctx.steam_tx.write().await.take();
+    } else if is_steam {
         log::info!("Lost connection to Steam, reconnecting...");
+        systemd::notify("STATUS=Lost connection to Steam, reconnecting");
         ctx.connected.store(false, Ordering::Relaxed);
         let mut steam_tx = ctx.steam_tx.write().await;
         steam_tx.take();
@@ -281,6 +383,68 @@ async fn handle_connection(ctx: Arc<Context>, stream: TcpStream) {
     }
 }
 
+/// Closes a connection when the server stops. Clients get an error for every request still
+/// waiting for Steam, and the payload is told to tear itself down.
+async fn close_connection(
+    ctx: &Context,
+    ws_stream: &mut WebSocketStream<TcpStream>,
+    tx: &UnboundedSender<String>,
+    is_steam: bool,
+) {
+    if is_steam {
+        if let Err(e) = ws_stream.send(Message::text("Shutdown")).await {
+            log::debug!("Failed to send shutdown message to Steam: {e}");
+        }
+    } else {
+        for res in take_pending_requests(ctx, tx).await {
+            send_message(ws_stream, &res).await;
+        }
+    }
+
+    let frame = CloseFrame {
+        code: CloseCode::Away,
+        reason: SHUTDOWN_REASON.into(),
+    };
+    if let Err(e) = ws_stream.close(Some(frame)).await {
+        log::debug!("Failed to send close frame: {e}");
+        return;
+    }
+
+    // Wait for the other side to confirm
+    while let Some(Ok(_)) = ws_stream.next().await {}
+}
+
+/// Forgets the requests `tx` sent to Steam that haven't been answered yet and returns a
+/// shutdown error for each of them.
+async fn take_pending_requests(
+    ctx: &Context,
+    tx: &UnboundedSender<String>,
+) -> Vec<serde_json::Value> {
+    let mut ids = ctx.message_ids.write().await;
+    let mut senders = ctx.message_senders.write().await;
+
+    let pending: Vec<u32> = senders
+        .iter()
+        .filter(|(_, sender)| sender.same_channel(tx))
+        .map(|(id, _)| *id)
+        .collect();
+
+    pending
+        .into_iter()
+        .map(|id| {
+            senders.remove(&id);
+            let mut res = json!({
+                "success": false,
+                "error": SHUTDOWN_REASON,
+            });
+            if let Some(client_id) = ids.remove(&id) {
+                res["messageId"] = client_id.into();
+            }
+            res
+        })
+        .collect()
+}
+
 async fn reconnect_to_steam(ctx: Arc<Context>) {
     tokio::time::sleep(Duration::from_millis(100)).await;
     let debugger_url = match try_get_debugger_url(Some(5)).await {
@@ -303,24 +467,222 @@ async fn reconnect_to_steam(ctx: Arc<Context>) {
     if !ctx.connected.load(Ordering::Relaxed) {
         log::error!("Failed to reconnect to Steam");
         _ = ctx.exit_tx.send(false);
+        return;
     }
 
     log::info!("Reconnected to Steam!");
 }
 
+type LocalHandler = for<'a> fn(
+    &'a Context,
+    &'a RpcRequest<'a>,
+    &'a Credential,
+    IpAddr,
+) -> BoxFuture<'a, serde_json::Value>;
+
+/// Finds the handler for a command implemented by Conductor itself. Returns `None` for commands
+/// that should be sent to Steam.
+fn local_handler(command: &str) -> Option<LocalHandler> {
+    match command {
+        "GetArtwork" | "SetArtwork" | "RemoveArtwork" => Some(|ctx, req, _, _| {
+            std::future::ready(artwork::handle_command(&ctx.steam, req.command, &req.args)).boxed()
+        }),
+        "RefreshToken" => Some(|ctx, req, credential, _| {
+            std::future::ready(refresh_token(ctx, req, credential)).boxed()
+        }),
+        "RequestPairing" => {
+            Some(|ctx, req, _, ip| std::future::ready(request_pairing(ctx, req, ip)).boxed())
+        }
+        "ConfirmPairing" => Some(|ctx, req, _, ip| confirm_pairing(ctx, req, ip).boxed()),
+        _ => None,
+    }
+}
+
+/// Makes a session token for the token the request was sent with.
+fn refresh_token(ctx: &Context, req: &RpcRequest, credential: &Credential) -> serde_json::Value {
+    let ttl = req
+        .args
+        .get("ttl")
+        .and_then(|ttl| ttl.as_u64())
+        .map(|ttl| ttl as u32);
+
+    match ctx.sessions.create(credential, ttl, Utc::now()) {
+        Ok((token, expires_at)) => {
+            log::debug!("Created a session token that expires at {expires_at}");
+            json!({
+                "success": true,
+                "token": token,
+                "expiresAt": expires_at.to_rfc3339(),
+            })
+        }
+        Err(error) => json!({
+            "success": false,
+            "error": error,
+        }),
+    }
+}
+
+/// Starts pairing a new device for `ip` and shows its code in the log and in Steam.
+fn request_pairing(ctx: &Context, req: &RpcRequest, ip: IpAddr) -> serde_json::Value {
+    if !ctx.pairing_enabled() {
+        return error_response(req, &PairingError::Disabled.to_string());
+    }
+
+    let device = Device {
+        name: req.args["deviceName"]
+            .as_str()
+            .unwrap_or_default()
+            .to_owned(),
+        scopes: req
+            .args
+            .get("scopes")
+            .and_then(|scopes| serde_json::from_value(scopes.clone()).ok()),
+    };
+    let name = device.name.clone();
+
+    match ctx.pairings.request(device, ip, Utc::now()) {
+        Ok(request) => {
+            log::info!(
+                "{name:?} at {ip} wants to pair, enter code {} on the device to allow it",
+                request.code
+            );
+            show_notification(
+                ctx,
+                "Pairing request",
+                &format!("Enter {} on {name} to pair it with Conductor", request.code),
+            );
+            json!({
+                "success": true,
+                "pairingId": request.id,
+                "expiresAt": request.expires_at.to_rfc3339(),
+            })
+        }
+        Err(e) => {
+            log::warn!("Rejected pairing request from {name:?} at {ip}: {e}");
+            error_response(req, &e.to_string())
+        }
+    }
+}
+
+/// Checks the code for a pairing request and gives the device a token. Wrong codes count toward
+/// the lockout of `ip` like wrong secrets.
+async fn confirm_pairing(ctx: &Context, req: &RpcRequest<'_>, ip: IpAddr) -> serde_json::Value {
+    let Some(limits) = ctx
+        .config
+        .borrow()
+        .auth
+        .as_ref()
+        .filter(|auth| auth.pairing)
+        .map(|auth| auth.lockout.clone())
+    else {
+        return error_response(req, &PairingError::Disabled.to_string());
+    };
+
+    let now = Utc::now();
+    if let Err(e) = ctx.lockout.check(&limits, ip, now) {
+        return error_response(req, &e.to_string());
+    }
+
+    let id = req.args["pairingId"].as_str().unwrap_or_default();
+    let code = req.args["code"].as_str().unwrap_or_default();
+    let device = match ctx.pairings.confirm(id, code, now) {
+        Ok(device) => device,
+        Err(e) => {
+            if e == PairingError::WrongCode {
+                ctx.lockout.fail(&limits, ip, now);
+            }
+            log::warn!("Failed to confirm pairing from {ip}: {e}");
+            return error_response(req, &e.to_string());
+        }
+    };
+
+    let name = pairing::token_name(ctx.config.borrow().auth.as_ref(), &device.name);
+    let (entry, token) = HashedToken::generate(name.clone(), None, None, device.scopes);
+    let settings_path = ctx.settings_path.clone();
+    let saved_entry = entry.clone();
+    let saved = tokio::task::spawn_blocking(move || {
+        settings_file::add_hashed_token(&settings_path, &saved_entry)
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+    if let Err(e) = saved {
+        log::error!("Failed to save the token for {name:?}: {e}");
+        return error_response(req, "Failed to save the token");
+    }
+
+    // Don't wait for the settings file to be reloaded, the device will use the token right away
+    ctx.config.send_modify(|config| {
+        config
+            .auth
+            .get_or_insert_default()
+            .hashed_tokens
+            .push(entry)
+    });
+    log::info!("Paired {name:?}, revoke it with `conductor token revoke {name:?}`");
+
+    json!({
+        "success": true,
+        "name": name,
+        "token": token,
+    })
+}
+
+/// Shows a notification in Steam if the payload is connected.
+fn show_notification(ctx: &Context, title: &str, body: &str) {
+    let Ok(steam_tx) = ctx.steam_tx.try_read() else {
+        return;
+    };
+    if let Some(steam_tx) = steam_tx.as_ref() {
+        let notification = json!({ "title": title, "body": body });
+        _ = steam_tx.send(format!("Notify:{notification}"));
+    }
+}
+
+/// Handles commands implemented by Conductor itself. Returns `None` for commands that should be sent to Steam.
+async fn handle_local_command(
+    ctx: &Context,
+    req: &RpcRequest<'_>,
+    credential: &Credential,
+    ip: IpAddr,
+) -> Option<serde_json::Value> {
+    let handler = local_handler(req.command)?;
+    Some(handler(ctx, req, credential, ip).await)
+}
+
+/// Handles a request from a client. Returns `false` if the client should be disconnected because
+/// its secret isn't valid anymore.
 async fn handle_client_message(
     ctx: Arc<Context>,
-    msg: &Utf8Bytes,
+    mut req: RpcRequest<'_>,
+    addr: SocketAddr,
     ws_stream: &mut WebSocketStream<TcpStream>,
-    tx: &mut UnboundedSender<String>,
-) {
+    tx: &UnboundedSender<String>,
+) -> bool {
+    let credential = match ctx.authorize(&req, addr.ip()) {
+        Ok(credential) => credential,
+        Err(e) => {
+            log::warn!("Rejected {} request from {addr}: {e}", req.command);
+            send_message(ws_stream, &error_response(&req, &e.to_string())).await;
+            return matches!(e, AuthError::OutOfScope(_));
+        }
+    };
+
+    if let Some(res) = validate_request(&req) {
+        log::warn!("Rejected {} request: {}", req.command, res["error"]);
+        send_message(ws_stream, &res).await;
+        return true;
+    }
+
+    if let Some(mut res) = handle_local_command(&ctx, &req, &credential, addr.ip()).await {
+        if let Some(id) = req.message_id {
+            res["messageId"] = id.into();
+        }
+        send_message(ws_stream, &res).await;
+        return true;
+    }
+
     let steam_tx = ctx.steam_tx.read().await;
     if let Some(steam_tx) = steam_tx.as_ref() {
-        let Ok(mut req) = serde_json::from_str::<RpcRequest>(msg) else {
-            log::error!("Failed to deserialize client message: {msg}");
-            return;
-        };
-
         let new_id = ctx.last_message_id.fetch_add(1, Ordering::Relaxed);
 
         let mut ids = ctx.message_ids.write().await;
@@ -331,13 +693,14 @@ async fn handle_client_message(
         }
 
         req.secret = Some(&ctx.steam_secret);
+        req.signature = None;
         req.message_id = Some(new_id);
 
         ctx.message_senders.write().await.insert(new_id, tx.clone());
 
         let Ok(req) = serde_json::to_string(&req) else {
             log::error!("Failed to serialize client message");
-            return;
+            return true;
         };
 
         if let Err(e) = steam_tx.send(req) {
@@ -347,15 +710,9 @@ async fn handle_client_message(
         }
     } else {
         log::warn!("Steam connection not available");
-        send_message(
-            ws_stream,
-            &json!({
-                "success": false,
-                "error": "Not connected to Steam",
-            }),
-        )
-        .await;
+        send_message(ws_stream, &error_response(&req, "Not connected to Steam")).await;
     }
+    true
 }
 
 async fn handle_steam_message(ctx: Arc<Context>, msg: &Utf8Bytes) {
@@ -385,13 +742,12 @@ async fn handle_steam_message(ctx: Arc<Context>, msg: &Utf8Bytes) {
 
     req.remove("messageId");
 
-    let ids = ctx.message_ids.read().await;
-    if let Some(id) = ids.get(&id) {
-        req.insert("messageId".to_string(), serde_json::Value::from(*id as u64));
+    // Each request gets one response, forget it so it isn't counted as pending anymore
+    if let Some(id) = ctx.message_ids.write().await.remove(&id) {
+        req.insert("messageId".to_string(), serde_json::Value::from(id as u64));
     }
 
-    let senders = ctx.message_senders.read().await;
-    let Some(tx) = senders.get(&id) else {
+    let Some(tx) = ctx.message_senders.write().await.remove(&id) else {
         log::warn!("No client channel found for id {id}");
         return;
     };
@@ -406,10 +762,270 @@ async fn handle_steam_message(ctx: Arc<Context>, msg: &Utf8Bytes) {
     }
 }
 
+/// Checks a request against the command schema. Returns an error response listing every invalid
+/// argument, or `None` if the request can be handled.
+fn validate_request(req: &RpcRequest) -> Option<serde_json::Value> {
+    let schema = conductor_schema::schema();
+
+    let Some(command) = schema.command(req.command) else {
+        return Some(error_response(
+            req,
+            &format!("Unknown command {}", req.command),
+        ));
+    };
+
+    let errors = schema.validate_args(command, &req.args);
+    if errors.is_empty() {
+        return None;
+    }
+
+    let summary: Vec<_> = errors.iter().map(ToString::to_string).collect();
+    let mut res = error_response(req, &format!("Invalid arguments: {}", summary.join(", ")));
+    res["fields"] = json!(errors);
+    Some(res)
+}
+
+/// Builds an error response for `req`, with its message ID so the client can match it.
+fn error_response(req: &RpcRequest, error: &str) -> serde_json::Value {
+    let mut res = json!({
+        "success": false,
+        "error": error,
+    });
+    if let Some(id) = req.message_id {
+        res["messageId"] = id.into();
+    }
+    res
+}
+
 async fn send_message(stream: &mut WebSocketStream<TcpStream>, msg: &serde_json::Value) {
-    if let Ok(msg_str) = serde_json::to_string(msg) {
-        if let Err(e) = stream.send(Message::text(msg_str)).await {
-            log::warn!("Failed to send message: {}", e);
+    if let Ok(msg_str) = serde_json::to_string(msg)
+        && let Err(e) = stream.send(Message::text(msg_str)).await
+    {
+        log::warn!("Failed to send message: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AuthConfig, LockoutConfig};
+    use conductor_schema::{Handler, schema};
+    use regex::Regex;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn server_commands_match_schema() {
+        for command in &schema().commands {
+            assert_eq!(
+                local_handler(&command.name).is_some(),
+                command.handler == Handler::Server,
+                "{} is handled by {:?} in the schema",
+                command.name,
+                command.handler
+            );
+        }
+    }
+
+    #[test]
+    fn payload_commands_match_schema() {
+        let payload = include_str!("js/payload.template.ts");
+        let re = Regex::new(r"(?m)^        (\w+): async").unwrap();
+
+        let handled: BTreeSet<&str> = re
+            .captures_iter(payload)
+            .map(|c| c.get(1).unwrap().as_str())
+            .collect();
+        let expected: BTreeSet<&str> = schema()
+            .commands_for(Handler::Steam)
+            .map(|c| c.name.as_str())
+            .collect();
+
+        assert_eq!(handled, expected, "payload handlers don't match the schema");
+    }
+
+    fn request(text: &str) -> Option<serde_json::Value> {
+        validate_request(&serde_json::from_str(text).unwrap())
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        assert_eq!(
+            request(r#"{"command":"RunApp","args":{"appId":"10"},"messageId":3}"#),
+            Some(json!({
+                "success": false,
+                "error": "Invalid arguments: appId must be a whole number from 0 to 4294967295",
+                "fields": [{ "field": "appId", "error": "must be a whole number from 0 to 4294967295" }],
+                "messageId": 3,
+            }))
+        );
+        assert_eq!(
+            request(r#"{"command":"FormatDisk","args":{}}"#),
+            Some(json!({ "success": false, "error": "Unknown command FormatDisk" }))
+        );
+        assert_eq!(request(r#"{"command":"RunApp","args":{"appId":10}}"#), None);
+    }
+
+    /// Server running on a free port with an empty Steam folder, its Steam secret is `secret`
+    struct TestServer {
+        addr: String,
+        url: String,
+        shutdown_tx: watch::Sender<bool>,
+        _steam_root: tempfile::TempDir,
+    }
+
+    async fn start_server(config: Config) -> TestServer {
+        let steam_root = tempfile::tempdir().unwrap();
+        std::fs::create_dir(steam_root.path().join("config")).unwrap();
+        let steam = Steam::locate(Some(steam_root.path())).unwrap();
+
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let (exit_tx, _exit_rx) = unbounded_channel();
+        let (init_tx, _init_rx) = unbounded_channel();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        tokio::spawn(serve(
+            addr.clone(),
+            "secret".to_owned(),
+            watch::channel(config).0,
+            String::new(),
+            String::new(),
+            steam,
+            exit_tx,
+            init_tx,
+            shutdown_rx,
+            Arc::default(),
+        ));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        TestServer {
+            url: format!("ws://{addr}"),
+            addr,
+            shutdown_tx,
+            _steam_root: steam_root,
         }
     }
+
+    #[tokio::test]
+    async fn shutdown_closes_connections() {
+        let TestServer {
+            addr,
+            url,
+            shutdown_tx,
+            _steam_root,
+        } = start_server(Config::default()).await;
+
+        let (mut payload, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+        payload.send(Message::text("init:secret")).await.unwrap();
+        assert_eq!(
+            payload.next().await.unwrap().unwrap(),
+            Message::text("Ready")
+        );
+
+        // Steam never answers this one
+        let (mut client, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+        client
+            .send(Message::text(
+                r#"{"command":"RunApp","args":{"appId":10},"messageId":7}"#,
+            ))
+            .await
+            .unwrap();
+        payload.next().await.unwrap().unwrap();
+
+        shutdown_tx.send(true).unwrap();
+
+        let res = client.next().await.unwrap().unwrap().into_text().unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&res).unwrap(),
+            json!({ "success": false, "error": SHUTDOWN_REASON, "messageId": 7 })
+        );
+        let Message::Close(Some(frame)) = client.next().await.unwrap().unwrap() else {
+            panic!("expected a close frame");
+        };
+        assert_eq!(frame.code, CloseCode::Away);
+        assert_eq!(frame.reason, SHUTDOWN_REASON);
+
+        assert_eq!(
+            payload.next().await.unwrap().unwrap(),
+            Message::text("Shutdown")
+        );
+        assert!(matches!(
+            payload.next().await.unwrap().unwrap(),
+            Message::Close(Some(_))
+        ));
+
+        // Let the server finish the close handshakes
+        drop((client, payload));
+        tokio::time::timeout(Duration::from_secs(5), shutdown_tx.closed())
+            .await
+            .expect("server should stop");
+        assert!(TcpStream::connect(&addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn wrong_pairing_codes_lock_out() {
+        let config = Config {
+            auth: Some(AuthConfig {
+                enabled: true,
+                pairing: true,
+                lockout: LockoutConfig {
+                    max_failures: 2,
+                    exempt: Vec::new(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let server = start_server(config).await;
+        let (mut client, _) = tokio_tungstenite::connect_async(&server.url).await.unwrap();
+
+        let mut call = async |command: &str, args: serde_json::Value| {
+            let req = json!({ "command": command, "args": args });
+            client.send(Message::text(req.to_string())).await.unwrap();
+            let res = client.next().await.unwrap().unwrap().into_text().unwrap();
+            serde_json::from_str::<serde_json::Value>(&res).unwrap()
+        };
+
+        let res = call("RequestPairing", json!({ "deviceName": "tv" })).await;
+        let confirm = json!({ "pairingId": res["pairingId"], "code": "wrong" });
+        for _ in 0..2 {
+            let res = call("ConfirmPairing", confirm.clone()).await;
+            assert_eq!(res["error"], "Wrong pairing code");
+        }
+        let res = call("ConfirmPairing", confirm).await;
+        let error = res["error"].as_str().unwrap();
+        assert!(error.starts_with("Too many failed attempts"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn rejects_browser_origins() {
+        use tokio_tungstenite::tungstenite::Error;
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+        let mut config = Config::default();
+        config.network.allowed_origins = vec!["http://localhost:3000".to_owned()];
+        let server = start_server(config).await;
+
+        let with_origin = |origin: &str| {
+            let mut request = server.url.as_str().into_client_request().unwrap();
+            request
+                .headers_mut()
+                .insert(ORIGIN, origin.parse().unwrap());
+            request
+        };
+
+        let err = tokio_tungstenite::connect_async(with_origin("https://evil.example"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Http(res) if res.status() == StatusCode::FORBIDDEN));
+
+        tokio_tungstenite::connect_async(with_origin("http://localhost:3000"))
+            .await
+            .unwrap();
+        tokio_tungstenite::connect_async(&server.url).await.unwrap();
+    }
 }
@@ -0,0 +1,89 @@
+/*
+ *  settings_file.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Edits `settings.toml` in place, keeping the user's comments and formatting.
+
+use crate::tokens::HashedToken;
+use std::fs::OpenOptions;
+use std::io::Write;
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table};
+
+/// Writes the settings file. New files are only readable by the current user since they can
+/// hold tokens, existing files keep their permissions. Fails with `AlreadyExists` unless
+/// `overwrite` is set.
+pub fn write(path: &str, contents: &str, overwrite: bool) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if overwrite {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options.open(path)?.write_all(contents.as_bytes())
+}
+
+/// Reads the settings file keeping its comments and formatting. A missing file is empty.
+pub fn read_document(settings_path: &str) -> Result<DocumentMut, String> {
+    let text = match std::fs::read_to_string(settings_path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read {settings_path}: {e}")),
+    };
+    text.parse()
+        .map_err(|e| format!("{settings_path} is not valid TOML:\n{e}"))
+}
+
+/// Writes a document read with [`read_document`] back to the settings file.
+pub fn write_document(settings_path: &str, doc: &DocumentMut) -> Result<(), String> {
+    write(settings_path, &doc.to_string(), true)
+        .map_err(|e| format!("Failed to write {settings_path}: {e}"))
+}
+
+/// Returns the array of tables `auth.<key>`, adding it if needed.
+pub fn auth_array<'a>(
+    doc: &'a mut DocumentMut,
+    key: &str,
+) -> Result<&'a mut ArrayOfTables, String> {
+    let auth = doc
+        .entry("auth")
+        .or_insert_with(|| Item::Table(Table::new()))
+        .as_table_mut()
+        .ok_or("`auth` is not a table")?;
+
+    auth.entry(key)
+        .or_insert_with(|| Item::ArrayOfTables(ArrayOfTables::new()))
+        .as_array_of_tables_mut()
+        .ok_or_else(|| format!("`auth.{key}` is not an array of tables"))
+}
+
+/// Returns `true` if a table in `list` has this `name`.
+pub fn has_entry(list: &ArrayOfTables, name: &str) -> bool {
+    list.iter()
+        .any(|entry| entry.get("name").and_then(|n| n.as_str()) == Some(name))
+}
+
+/// Turns a token or key into a table for [`auth_array`].
+pub fn to_table(entry: &impl serde::Serialize) -> Result<Table, String> {
+    toml_edit::ser::to_document(entry)
+        .map(|doc| doc.as_table().clone())
+        .map_err(|e| format!("Failed to serialize token: {e}"))
+}
+
+/// Adds a token to `[[auth.hashed_tokens]]` in the settings file.
+pub fn add_hashed_token(settings_path: &str, token: &HashedToken) -> Result<(), String> {
+    let mut doc = read_document(settings_path)?;
+    auth_array(&mut doc, "hashed_tokens")?.push(to_table(token)?);
+    write_document(settings_path, &doc)
+}
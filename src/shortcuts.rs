@@ -0,0 +1,500 @@
+/*
+ *  shortcuts.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::vdf::{self, Map, Value, VdfError};
+use serde::Serialize;
+use std::path::Path;
+use thiserror::Error;
+
+/// Returns the app ID Steam generates for a shortcut. `exe` should be the path exactly as it's
+/// stored in `shortcuts.vdf`, including the quotes.
+pub fn shortcut_app_id(exe: &str, name: &str) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(exe.as_bytes());
+    hasher.update(name.as_bytes());
+    hasher.finalize() | 0x8000_0000
+}
+
+/// Wraps a path in quotes like the Steam client does, leaving already quoted paths alone.
+pub fn quote_path(path: &str) -> String {
+    if path.is_empty() || (path.starts_with('"') && path.ends_with('"') && path.len() > 1) {
+        path.to_owned()
+    } else {
+        format!("\"{path}\"")
+    }
+}
+
+/// Single non-Steam game entry from `shortcuts.vdf`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Shortcut {
+    pub app_id: u32,
+    pub name: String,
+    pub exe: String,
+    pub start_dir: String,
+    pub icon: String,
+    pub shortcut_path: String,
+    pub launch_options: String,
+    pub is_hidden: bool,
+    pub allow_desktop_config: bool,
+    pub allow_overlay: bool,
+    pub open_vr: bool,
+    pub devkit: bool,
+    pub devkit_game_id: String,
+    pub devkit_override_app_id: u32,
+    pub last_play_time: u32,
+    pub flatpak_app_id: String,
+    pub sort_as: String,
+    pub tags: Vec<String>,
+    /// Keys this version doesn't know about, written back as they were
+    #[serde(skip)]
+    pub extra: Map,
+}
+
+const KNOWN_KEYS: [&str; 18] = [
+    "appid",
+    "AppName",
+    "Exe",
+    "StartDir",
+    "icon",
+    "ShortcutPath",
+    "LaunchOptions",
+    "IsHidden",
+    "AllowDesktopConfig",
+    "AllowOverlay",
+    "OpenVR",
+    "Devkit",
+    "DevkitGameID",
+    "DevkitOverrideAppID",
+    "LastPlayTime",
+    "FlatpakAppID",
+    "sortas",
+    "tags",
+];
+
+impl Shortcut {
+    /// Creates a shortcut with the same defaults the Steam client uses. `exe` and `start_dir` get
+    /// quoted, the start dir defaults to the exe's parent folder and the name to its file stem.
+    pub fn new(exe: &str, name: Option<&str>, start_dir: Option<&str>) -> Self {
+        let unquoted = exe.trim_matches('"');
+        let path = Path::new(unquoted);
+
+        let name = name.map(str::to_owned).unwrap_or_else(|| {
+            path.file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+        let start_dir = start_dir.map(str::to_owned).unwrap_or_else(|| {
+            path.parent()
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+
+        let exe = quote_path(exe);
+        let app_id = shortcut_app_id(&exe, &name);
+
+        Self {
+            app_id,
+            name,
+            exe,
+            start_dir: quote_path(&start_dir),
+            allow_desktop_config: true,
+            allow_overlay: true,
+            ..Default::default()
+        }
+    }
+
+    fn from_map(map: &Map) -> Self {
+        let string = |key| map.get_str(key).unwrap_or_default().to_owned();
+        let int = |key| map.get_i32(key).unwrap_or_default();
+
+        let tags = map
+            .get_map("tags")
+            .map(|tags| {
+                tags.iter()
+                    .filter_map(|(_, v)| match v {
+                        Value::String(s) => Some(s.clone()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let extra = map
+            .iter()
+            .filter(|(k, _)| !KNOWN_KEYS.iter().any(|known| known.eq_ignore_ascii_case(k)))
+            .map(|(k, v)| (k.to_owned(), v.clone()))
+            .collect();
+
+        // Older Steam versions and some tools leave the ID out, Steam derives it the same way
+        let name = string("AppName");
+        let exe = string("Exe");
+        let app_id = map
+            .get_i32("appid")
+            .map_or_else(|| shortcut_app_id(&exe, &name), |id| id as u32);
+
+        Self {
+            app_id,
+            name,
+            exe,
+            start_dir: string("StartDir"),
+            icon: string("icon"),
+            shortcut_path: string("ShortcutPath"),
+            launch_options: string("LaunchOptions"),
+            is_hidden: int("IsHidden") != 0,
+            allow_desktop_config: int("AllowDesktopConfig") != 0,
+            allow_overlay: int("AllowOverlay") != 0,
+            open_vr: int("OpenVR") != 0,
+            devkit: int("Devkit") != 0,
+            devkit_game_id: string("DevkitGameID"),
+            devkit_override_app_id: int("DevkitOverrideAppID") as u32,
+            last_play_time: int("LastPlayTime") as u32,
+            flatpak_app_id: string("FlatpakAppID"),
+            sort_as: string("sortas"),
+            tags,
+            extra,
+        }
+    }
+
+    fn to_map(&self) -> Map {
+        let string = |key: &str, value: &str| (key.to_owned(), Value::String(value.to_owned()));
+        let int = |key: &str, value: u32| (key.to_owned(), Value::Int32(value as i32));
+
+        let tags = self
+            .tags
+            .iter()
+            .enumerate()
+            .map(|(i, tag)| (i.to_string(), Value::String(tag.clone())))
+            .collect();
+
+        let mut map: Map = [
+            int("appid", self.app_id),
+            string("AppName", &self.name),
+            string("Exe", &self.exe),
+            string("StartDir", &self.start_dir),
+            string("icon", &self.icon),
+            string("ShortcutPath", &self.shortcut_path),
+            string("LaunchOptions", &self.launch_options),
+            int("IsHidden", self.is_hidden as u32),
+            int("AllowDesktopConfig", self.allow_desktop_config as u32),
+            int("AllowOverlay", self.allow_overlay as u32),
+            int("OpenVR", self.open_vr as u32),
+            int("Devkit", self.devkit as u32),
+            string("DevkitGameID", &self.devkit_game_id),
+            int("DevkitOverrideAppID", self.devkit_override_app_id),
+            int("LastPlayTime", self.last_play_time),
+            string("FlatpakAppID", &self.flatpak_app_id),
+            string("sortas", &self.sort_as),
+            ("tags".to_owned(), Value::Map(tags)),
+        ]
+        .into_iter()
+        .collect();
+
+        for (key, value) in self.extra.iter() {
+            map.insert(key, value.clone());
+        }
+
+        map
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ShortcutsError {
+    #[error("Error reading file: {0}")]
+    ReadFile(std::io::Error),
+    #[error("Error writing file: {0}")]
+    WriteFile(std::io::Error),
+    #[error("Invalid shortcuts file: {0}")]
+    Parse(VdfError),
+    #[error("Invalid shortcuts file: missing `shortcuts` root key")]
+    MissingRoot,
+    #[error("A shortcut with app ID {0} already exists")]
+    Duplicate(u32),
+    #[error("No shortcut with app ID {0}")]
+    NotFound(u32),
+}
+
+/// Contents of a user's `shortcuts.vdf`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Shortcuts {
+    entries: Vec<Shortcut>,
+}
+
+impl Shortcuts {
+    /// Loads shortcuts from `path`. A missing file is treated as an empty list, which is what
+    /// Steam does for users that never added a non-Steam game.
+    pub fn load(path: &Path) -> Result<Self, ShortcutsError> {
+        match std::fs::read(path) {
+            Ok(bytes) => Self::from_bytes(&bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(ShortcutsError::ReadFile(e)),
+        }
+    }
+
+    /// Writes shortcuts to `path` through a temporary file so Steam never sees a half written file.
+    pub fn save(&self, path: &Path) -> Result<(), ShortcutsError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(ShortcutsError::WriteFile)?;
+        }
+
+        let tmp_path = path.with_extension("vdf.tmp");
+        std::fs::write(&tmp_path, self.to_bytes()).map_err(ShortcutsError::WriteFile)?;
+        std::fs::rename(&tmp_path, path).map_err(ShortcutsError::WriteFile)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ShortcutsError> {
+        let root = vdf::read_binary(bytes).map_err(ShortcutsError::Parse)?;
+        let Some(list) = root.get_map("shortcuts") else {
+            return Err(ShortcutsError::MissingRoot);
+        };
+
+        let entries = list
+            .iter()
+            .filter_map(|(_, v)| match v {
+                Value::Map(m) => Some(Shortcut::from_map(m)),
+                _ => None,
+            })
+            .collect();
+
+        Ok(Self { entries })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let list = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (i.to_string(), Value::Map(s.to_map())))
+            .collect();
+
+        let mut root = Map::new();
+        root.insert("shortcuts", Value::Map(list));
+
+        vdf::write_binary(&root)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Shortcut> {
+        self.entries.iter()
+    }
+
+    pub fn get(&self, app_id: u32) -> Option<&Shortcut> {
+        self.entries.iter().find(|s| s.app_id == app_id)
+    }
+
+    pub fn get_mut(&mut self, app_id: u32) -> Option<&mut Shortcut> {
+        self.entries.iter_mut().find(|s| s.app_id == app_id)
+    }
+
+    /// Adds a shortcut and returns its app ID. Steam won't show two entries with the same ID so
+    /// adding the same exe and name twice is an error.
+    pub fn add(&mut self, shortcut: Shortcut) -> Result<u32, ShortcutsError> {
+        if self.get(shortcut.app_id).is_some() {
+            return Err(ShortcutsError::Duplicate(shortcut.app_id));
+        }
+
+        let app_id = shortcut.app_id;
+        self.entries.push(shortcut);
+        Ok(app_id)
+    }
+
+    pub fn remove(&mut self, app_id: u32) -> Result<Shortcut, ShortcutsError> {
+        let Some(idx) = self.entries.iter().position(|s| s.app_id == app_id) else {
+            return Err(ShortcutsError::NotFound(app_id));
+        };
+
+        Ok(self.entries.remove(idx))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds one shortcut entry field by field in the order the Steam client writes them.
+    fn steam_entry(out: &mut Vec<u8>, idx: &str, app_id: u32, name: &str, exe: &str, dir: &str) {
+        let string = |out: &mut Vec<u8>, key: &str, value: &str| {
+            out.push(0x01);
+            out.extend_from_slice(key.as_bytes());
+            out.push(0);
+            out.extend_from_slice(value.as_bytes());
+            out.push(0);
+        };
+        let int = |out: &mut Vec<u8>, key: &str, value: u32| {
+            out.push(0x02);
+            out.extend_from_slice(key.as_bytes());
+            out.push(0);
+            out.extend_from_slice(&value.to_le_bytes());
+        };
+
+        out.push(0x00);
+        out.extend_from_slice(idx.as_bytes());
+        out.push(0);
+        int(out, "appid", app_id);
+        string(out, "AppName", name);
+        string(out, "Exe", exe);
+        string(out, "StartDir", dir);
+        string(out, "icon", "");
+        string(out, "ShortcutPath", "");
+        string(out, "LaunchOptions", "-fullscreen");
+        int(out, "IsHidden", 0);
+        int(out, "AllowDesktopConfig", 1);
+        int(out, "AllowOverlay", 1);
+        int(out, "OpenVR", 0);
+        int(out, "Devkit", 0);
+        string(out, "DevkitGameID", "");
+        int(out, "DevkitOverrideAppID", 0);
+        int(out, "LastPlayTime", 1714000000);
+        string(out, "FlatpakAppID", "");
+        string(out, "sortas", "");
+        out.extend_from_slice(b"\x00tags\x00\x010\x00favorite\x00\x08");
+        out.push(0x08);
+    }
+
+    fn steam_file() -> Vec<u8> {
+        let mut out = b"\x00shortcuts\x00".to_vec();
+        steam_entry(
+            &mut out,
+            "0",
+            3_524_151_011,
+            "Game",
+            "\"/games/game.sh\"",
+            "\"/games/\"",
+        );
+        steam_entry(
+            &mut out,
+            "1",
+            2_864_396_543,
+            "Other",
+            "\"C:\\Other\\other.exe\"",
+            "\"C:\\Other\\\"",
+        );
+        out.extend_from_slice(b"\x08\x08");
+        out
+    }
+
+    #[test]
+    fn round_trip_steam_file() {
+        let bytes = steam_file();
+        let shortcuts = Shortcuts::from_bytes(&bytes).unwrap();
+
+        assert_eq!(shortcuts.iter().count(), 2);
+        let game = shortcuts.get(3_524_151_011).unwrap();
+        assert_eq!(game.name, "Game");
+        assert_eq!(game.launch_options, "-fullscreen");
+        assert_eq!(game.tags, vec!["favorite".to_owned()]);
+        assert!(game.allow_overlay);
+
+        assert_eq!(shortcuts.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn round_trip_keeps_unknown_keys() {
+        let mut shortcuts = Shortcuts::default();
+        let mut shortcut = Shortcut::new("/usr/bin/foo", None, None);
+        shortcut.extra.insert("FutureKey", Value::Int32(7));
+        shortcuts.add(shortcut).unwrap();
+
+        let parsed = Shortcuts::from_bytes(&shortcuts.to_bytes()).unwrap();
+
+        assert_eq!(parsed, shortcuts);
+    }
+
+    #[test]
+    fn derives_missing_app_ids() {
+        let bytes = b"\x00shortcuts\x00\x000\x00\x01AppName\x00Game\x00\x01Exe\x00\"/games/game.sh\"\x00\x08\x08\x08";
+        let shortcuts = Shortcuts::from_bytes(bytes).unwrap();
+
+        let app_id = shortcut_app_id("\"/games/game.sh\"", "Game");
+        assert_eq!(shortcuts.iter().next().unwrap().app_id, app_id);
+
+        let saved = Shortcuts::from_bytes(&shortcuts.to_bytes()).unwrap();
+        assert!(saved.get(app_id).is_some());
+    }
+
+    #[test]
+    fn empty_file() {
+        let shortcuts = Shortcuts::from_bytes(b"\x00shortcuts\x00\x08\x08").unwrap();
+        assert!(shortcuts.is_empty());
+        assert_eq!(shortcuts.to_bytes(), b"\x00shortcuts\x00\x08\x08");
+    }
+
+    #[test]
+    fn add_and_remove() {
+        let mut shortcuts = Shortcuts::from_bytes(&steam_file()).unwrap();
+
+        let app_id = shortcuts
+            .add(Shortcut::new("/games/new.sh", Some("New"), None))
+            .unwrap();
+        assert!(matches!(
+            shortcuts.add(Shortcut::new("/games/new.sh", Some("New"), None)),
+            Err(ShortcutsError::Duplicate(id)) if id == app_id
+        ));
+
+        let removed = shortcuts.remove(3_524_151_011).unwrap();
+        assert_eq!(removed.name, "Game");
+
+        let reparsed = Shortcuts::from_bytes(&shortcuts.to_bytes()).unwrap();
+        assert_eq!(reparsed.iter().count(), 2);
+        assert!(reparsed.get(app_id).is_some());
+        assert!(matches!(
+            shortcuts.remove(3_524_151_011),
+            Err(ShortcutsError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn new_shortcut_defaults() {
+        let shortcut = Shortcut::new("/games/foo/run.sh", None, None);
+
+        assert_eq!(shortcut.name, "run");
+        assert_eq!(shortcut.exe, "\"/games/foo/run.sh\"");
+        assert_eq!(shortcut.start_dir, "\"/games/foo\"");
+        assert_eq!(
+            shortcut.app_id,
+            shortcut_app_id("\"/games/foo/run.sh\"", "run")
+        );
+    }
+
+    #[test]
+    fn app_ids_match_steam() {
+        // Steam hashes the quoted exe followed by the name with plain CRC-32 and sets the top bit
+        assert_eq!(
+            shortcut_app_id("\"C:\\Games\\Test\\test.exe\"", "Test"),
+            crc32_reference("\"C:\\Games\\Test\\test.exe\"Test") | 0x8000_0000
+        );
+        assert!(shortcut_app_id("\"/a\"", "b") & 0x8000_0000 != 0);
+    }
+
+    /// Bitwise CRC-32 used to double check the table driven one
+    fn crc32_reference(s: &str) -> u32 {
+        let mut crc = 0xFFFF_FFFFu32;
+        for b in s.bytes() {
+            crc ^= b as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote_path("/a b/c"), "\"/a b/c\"");
+        assert_eq!(quote_path("\"/a\""), "\"/a\"");
+        assert_eq!(quote_path(""), "");
+    }
+}
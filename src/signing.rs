@@ -0,0 +1,141 @@
+/*
+ *  signing.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Checks HMAC-signed requests. The format is described in [`conductor_client::signing`].
+
+use crate::config::AuthConfig;
+use crate::tokens::{AuthError, Credential};
+use chrono::{DateTime, Utc};
+use conductor_client::signing::{Signature, canonical_request, verify};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// How far a signature's timestamp can be from the server's clock, in seconds. Nonces are
+/// remembered until their timestamp is too old to be accepted.
+const MAX_CLOCK_SKEW: i64 = 5 * 60;
+
+/// Longest nonce the server accepts
+const MAX_NONCE_LEN: usize = 128;
+
+/// Nonces of recently accepted signatures, with their timestamps
+#[derive(Default)]
+pub struct NonceCache(Mutex<HashMap<(String, String), i64>>);
+
+impl NonceCache {
+    /// Remembers a nonce. Returns `false` if it was already used with the same key.
+    fn insert(&self, key_id: &str, nonce: &str, timestamp: i64, now: i64) -> bool {
+        let mut nonces = self.0.lock().unwrap();
+        nonces.retain(|_, seen| *seen >= now - MAX_CLOCK_SKEW);
+        nonces
+            .insert((key_id.to_owned(), nonce.to_owned()), timestamp)
+            .is_none()
+    }
+}
+
+/// Checks a signed request for `command` and returns the credential of the key that signed it.
+pub fn authorize(
+    auth: Option<&AuthConfig>,
+    nonces: &NonceCache,
+    signature: &Signature,
+    command: &str,
+    args: &serde_json::Value,
+    now: DateTime<Utc>,
+) -> Result<Credential, AuthError> {
+    let Some(auth) = auth.filter(|auth| auth.enabled) else {
+        return Ok(Credential::Anonymous);
+    };
+
+    let key = auth
+        .signing_keys
+        .iter()
+        .find(|key| key.name == signature.key_id)
+        .ok_or(AuthError::BadSignature)?;
+
+    if signature.nonce.is_empty() || signature.nonce.len() > MAX_NONCE_LEN {
+        return Err(AuthError::BadSignature);
+    }
+
+    let canonical = canonical_request(
+        &signature.key_id,
+        signature.timestamp,
+        &signature.nonce,
+        command,
+        args,
+    );
+    if !verify(&key.key, &canonical, &signature.mac) {
+        return Err(AuthError::BadSignature);
+    }
+
+    // Only checked once the signature is valid, so nobody else can fill the cache
+    let now_secs = now.timestamp();
+    if signature.timestamp.abs_diff(now_secs) > MAX_CLOCK_SKEW as u64 {
+        return Err(AuthError::StaleTimestamp);
+    }
+    if !nonces.insert(
+        &signature.key_id,
+        &signature.nonce,
+        signature.timestamp,
+        now_secs,
+    ) {
+        return Err(AuthError::Replayed);
+    }
+
+    key.authorize(command, now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokens::SharedKey;
+    use chrono::TimeDelta;
+    use conductor_client::SigningKey;
+    use serde_json::json;
+
+    #[test]
+    fn checks_signatures() {
+        let shared = SharedKey::generate("tv".to_owned(), None, None, None);
+        let auth = AuthConfig {
+            enabled: true,
+            signing_keys: vec![shared.clone()],
+            ..Default::default()
+        };
+        let nonces = NonceCache::default();
+        let client = SigningKey {
+            id: shared.name.clone(),
+            key: shared.key.clone(),
+        };
+
+        let args = json!({ "appId": 730 });
+        let signature = client.sign("RunApp", &args);
+        let now = DateTime::from_timestamp(signature.timestamp, 0).unwrap();
+        let check = |signature: &Signature, args: &serde_json::Value, now| {
+            authorize(Some(&auth), &nonces, signature, "RunApp", args, now).map(|_| ())
+        };
+
+        assert_eq!(
+            check(&signature, &json!({ "appId": 440 }), now),
+            Err(AuthError::BadSignature)
+        );
+        assert_eq!(
+            check(&signature, &args, now + TimeDelta::minutes(10)),
+            Err(AuthError::StaleTimestamp)
+        );
+        assert_eq!(check(&signature, &args, now), Ok(()));
+        assert_eq!(check(&signature, &args, now), Err(AuthError::Replayed));
+
+        let wrong_key = SigningKey {
+            key: "not the key".to_owned(),
+            ..client
+        };
+        assert_eq!(
+            check(&wrong_key.sign("RunApp", &args), &args, now),
+            Err(AuthError::BadSignature)
+        );
+    }
+}
@@ -0,0 +1,539 @@
+/*
+ *  steam.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use crate::vdf::{self, VdfError};
+use cfg_if::cfg_if;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::process::Command;
+
+/// Offset between 64-bit Steam IDs of individual accounts and their 32-bit account IDs
+const STEAM_ID_BASE: u64 = 76561197960265728;
+
+/// How Steam was installed. Matters when we need to start it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallKind {
+    Native,
+    Flatpak,
+    Snap,
+}
+
+/// Steam account that logged in on this device, read from `config/loginusers.vdf`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SteamUser {
+    pub steam_id: u64,
+    pub account_id: u32,
+    pub account_name: String,
+    pub persona_name: String,
+    pub most_recent: bool,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum SteamError {
+    #[error("Could not find a Steam installation, set `steam_path` in the settings")]
+    NotFound,
+    #[error("`steam_path` is set to {0}, which is not a Steam installation")]
+    InvalidPath(PathBuf),
+    #[error("Steam is not supported on this platform")]
+    Unsupported,
+    #[error("Error reading loginusers.vdf: {0}")]
+    ReadUsers(std::io::Error),
+    #[error("Error parsing loginusers.vdf: {0}")]
+    ParseUsers(VdfError),
+    #[error("No Steam users have logged in on this device")]
+    NoUsers,
+    #[error("No Steam user with account ID {0} on this device")]
+    UnknownUser(u32),
+    #[error("Failed to start Steam: {0}")]
+    Launch(std::io::Error),
+    #[error("Steam did not shut down after {0} seconds")]
+    ShutdownTimeout(u64),
+    #[error("Steam did not start after {0} seconds")]
+    StartTimeout(u64),
+}
+
+/// A Steam installation on this device.
+#[derive(Debug, Clone)]
+pub struct Steam {
+    root: PathBuf,
+    kind: InstallKind,
+}
+
+#[allow(deprecated)] // behavior fixed in rust 1.85, will be un-deprecated
+pub(crate) fn home_dir() -> Option<PathBuf> {
+    std::env::home_dir()
+}
+
+/// Places Steam is usually installed to, in the order they're checked.
+fn candidates() -> Vec<(PathBuf, InstallKind)> {
+    #[allow(unused_mut)] // stays empty on unsupported platforms
+    let mut list = Vec::new();
+
+    cfg_if! {
+        if #[cfg(windows)] {
+            use winreg::RegKey;
+            use winreg::enums::HKEY_CURRENT_USER;
+
+            if let Ok(key) = RegKey::predef(HKEY_CURRENT_USER).open_subkey(r"Software\Valve\Steam")
+                && let Ok(path) = key.get_value::<String, _>("SteamPath")
+            {
+                list.push((PathBuf::from(path), InstallKind::Native));
+            }
+
+            list.push((PathBuf::from(r"C:\Program Files (x86)\Steam"), InstallKind::Native));
+            list.push((PathBuf::from(r"C:\Program Files\Steam"), InstallKind::Native));
+        } else if #[cfg(target_os = "linux")] {
+            if let Some(home) = home_dir() {
+                list.push((home.join(".steam/steam"), InstallKind::Native));
+                list.push((home.join(".local/share/Steam"), InstallKind::Native));
+                list.push((home.join(".steam/debian-installation"), InstallKind::Native));
+                list.push((
+                    home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"),
+                    InstallKind::Flatpak,
+                ));
+                list.push((
+                    home.join(".var/app/com.valvesoftware.Steam/.steam/steam"),
+                    InstallKind::Flatpak,
+                ));
+                list.push((home.join("snap/steam/common/.local/share/Steam"), InstallKind::Snap));
+            }
+        } else if #[cfg(target_os = "macos")] {
+            if let Some(home) = home_dir() {
+                list.push((home.join("Library/Application Support/Steam"), InstallKind::Native));
+            }
+        }
+    }
+
+    list
+}
+
+/// Looks for a process named `steam` in a procfs folder. `comm` holds the executable name,
+/// Flatpak processes are visible here too.
+#[cfg(target_os = "linux")]
+fn has_steam_process(proc: &Path) -> bool {
+    let Ok(entries) = std::fs::read_dir(proc) else {
+        return false;
+    };
+
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.file_name()
+                .to_string_lossy()
+                .bytes()
+                .all(|b| b.is_ascii_digit())
+        })
+        .any(|e| {
+            std::fs::read_to_string(e.path().join("comm"))
+                .is_ok_and(|comm| comm.trim_end() == "steam")
+        })
+}
+
+/// Checks if the Steam client process is running on this device.
+pub fn is_running() -> bool {
+    cfg_if! {
+        if #[cfg(target_os = "linux")] {
+            has_steam_process(Path::new("/proc"))
+        } else if #[cfg(windows)] {
+            std::process::Command::new("tasklist")
+                .args(["/FI", "IMAGENAME eq steam.exe", "/NH"])
+                .output()
+                .is_ok_and(|out| {
+                    String::from_utf8_lossy(&out.stdout)
+                        .to_ascii_lowercase()
+                        .contains("steam.exe")
+                })
+        } else if #[cfg(target_os = "macos")] {
+            std::process::Command::new("pgrep")
+                .args(["-x", "steam_osx"])
+                .stdout(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success())
+        } else {
+            false
+        }
+    }
+}
+
+/// Tells Flatpak and Snap installs apart from a path, used for `steam_path` where we can't know.
+fn guess_kind(path: &Path) -> InstallKind {
+    let path = path.to_string_lossy();
+    if path.contains("com.valvesoftware.Steam") {
+        InstallKind::Flatpak
+    } else if path.contains("snap/steam") {
+        InstallKind::Snap
+    } else {
+        InstallKind::Native
+    }
+}
+
+/// Checks for the folders every Steam install has after its first start.
+fn is_steam_root(path: &Path) -> bool {
+    path.join("config").is_dir() || path.join("steamapps").is_dir()
+}
+
+impl Steam {
+    /// Finds Steam on this device. If `steam_path` is set it's used as is, otherwise the usual install
+    /// locations are checked, including Flatpak and Snap on Linux.
+    pub fn locate(steam_path: Option<&Path>) -> Result<Self, SteamError> {
+        if let Some(path) = steam_path {
+            if !is_steam_root(path) {
+                return Err(SteamError::InvalidPath(path.to_path_buf()));
+            }
+
+            return Ok(Self {
+                root: path.to_path_buf(),
+                kind: guess_kind(path),
+            });
+        }
+
+        let candidates = candidates();
+        if candidates.is_empty() {
+            return Err(SteamError::Unsupported);
+        }
+
+        Self::find(candidates).ok_or(SteamError::NotFound)
+    }
+
+    /// Returns the first of `candidates` that is a Steam install.
+    fn find(candidates: Vec<(PathBuf, InstallKind)>) -> Option<Self> {
+        candidates
+            .into_iter()
+            .find(|(path, _)| is_steam_root(path))
+            .map(|(path, kind)| {
+                // `~/.steam/steam` is a symlink to the real folder
+                let root = std::fs::canonicalize(&path).unwrap_or(path);
+                log::debug!("Found Steam at {}", root.display());
+                Self { root, kind }
+            })
+    }
+
+    /// Folder Steam is installed to.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn kind(&self) -> InstallKind {
+        self.kind
+    }
+
+    /// Lists the accounts that logged in on this device, most recent first.
+    pub fn users(&self) -> Result<Vec<SteamUser>, SteamError> {
+        let text = std::fs::read_to_string(self.root.join("config/loginusers.vdf"))
+            .map_err(SteamError::ReadUsers)?;
+        let root = vdf::read_text(&text).map_err(SteamError::ParseUsers)?;
+
+        let Some(users) = root.get_map("users") else {
+            return Ok(Vec::new());
+        };
+
+        let mut list = users
+            .iter()
+            .filter_map(|(id, value)| {
+                let vdf::Value::Map(user) = value else {
+                    return None;
+                };
+                let steam_id = id.parse::<u64>().ok()?;
+                let string = |key| user.get_str(key).unwrap_or_default().to_owned();
+
+                Some(SteamUser {
+                    steam_id,
+                    account_id: steam_id.checked_sub(STEAM_ID_BASE)? as u32,
+                    account_name: string("AccountName"),
+                    persona_name: string("PersonaName"),
+                    most_recent: user.get_str("MostRecent") == Some("1"),
+                    timestamp: user
+                        .get_str("Timestamp")
+                        .and_then(|t| t.parse().ok())
+                        .unwrap_or_default(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        list.sort_by(|a, b| {
+            b.most_recent
+                .cmp(&a.most_recent)
+                .then(b.timestamp.cmp(&a.timestamp))
+        });
+
+        Ok(list)
+    }
+
+    /// Returns the user with `account_id`, or the one that logged in most recently if it's not set.
+    pub fn user(&self, account_id: Option<u32>) -> Result<SteamUser, SteamError> {
+        let users = self.users()?;
+
+        match account_id {
+            Some(id) => users
+                .into_iter()
+                .find(|u| u.account_id == id)
+                .ok_or(SteamError::UnknownUser(id)),
+            None => users.into_iter().next().ok_or(SteamError::NoUsers),
+        }
+    }
+
+    /// Per-user config folder, `userdata/<accountid>/config`.
+    pub fn user_config_dir(&self, account_id: u32) -> PathBuf {
+        self.root
+            .join("userdata")
+            .join(account_id.to_string())
+            .join("config")
+    }
+
+    pub fn shortcuts_path(&self, account_id: u32) -> PathBuf {
+        self.user_config_dir(account_id).join("shortcuts.vdf")
+    }
+
+    pub fn grid_dir(&self, account_id: u32) -> PathBuf {
+        self.user_config_dir(account_id).join("grid")
+    }
+
+    /// Marker file that makes Steam open the CEF debugger port on its next start.
+    pub fn cef_debugging_marker(&self) -> PathBuf {
+        self.root.join(".cef-enable-remote-debugging")
+    }
+
+    /// Builds the command that starts this Steam install. Extra arguments are passed to Steam.
+    fn command(&self) -> Command {
+        let mut cmd = match self.kind {
+            InstallKind::Flatpak => {
+                let mut cmd = Command::new("flatpak");
+                cmd.args(["run", "com.valvesoftware.Steam"]);
+                cmd
+            }
+            InstallKind::Snap => {
+                let mut cmd = Command::new("snap");
+                cmd.args(["run", "steam"]);
+                cmd
+            }
+            InstallKind::Native => {
+                cfg_if! {
+                    if #[cfg(windows)] {
+                        Command::new(self.root.join("steam.exe"))
+                    } else if #[cfg(target_os = "macos")] {
+                        let mut cmd = Command::new("open");
+                        cmd.args(["-a", "Steam", "--args"]);
+                        cmd
+                    } else {
+                        // Prefer the distro launcher, it sets up the runtime before starting `steam.sh`
+                        let launcher = ["/usr/bin/steam", "/usr/games/steam"]
+                            .into_iter()
+                            .map(PathBuf::from)
+                            .find(|p| p.exists());
+                        Command::new(launcher.unwrap_or_else(|| self.root.join("steam.sh")))
+                    }
+                }
+            }
+        };
+
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        cmd
+    }
+
+    /// Starts Steam and waits for its process to show up, but not for it to finish loading.
+    pub async fn launch(&self) -> Result<(), SteamError> {
+        log::info!("Starting Steam...");
+        self.command().spawn().map_err(SteamError::Launch)?;
+
+        let timeout = Duration::from_secs(30);
+        let start = tokio::time::Instant::now();
+        while !is_running() {
+            if start.elapsed() > timeout {
+                return Err(SteamError::StartTimeout(timeout.as_secs()));
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+
+        Ok(())
+    }
+
+    /// Asks Steam to close and waits until the process is gone.
+    pub async fn shutdown(&self, timeout: Duration) -> Result<(), SteamError> {
+        log::info!("Shutting down Steam...");
+        self.command()
+            .arg("-shutdown")
+            .status()
+            .await
+            .map_err(SteamError::Launch)?;
+
+        let start = tokio::time::Instant::now();
+        while is_running() {
+            if start.elapsed() > timeout {
+                return Err(SteamError::ShutdownTimeout(timeout.as_secs()));
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+
+        Ok(())
+    }
+
+    /// Closes Steam gracefully and starts it again, used to pick up the CEF debugging marker.
+    pub async fn restart(&self) -> Result<(), SteamError> {
+        if is_running() {
+            self.shutdown(Duration::from_secs(30)).await?;
+        }
+        // Steam leaves helper processes around for a moment after the main one exits
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        self.launch().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steam_root(path: &Path) -> PathBuf {
+        std::fs::create_dir_all(path.join("config")).unwrap();
+        path.to_path_buf()
+    }
+
+    #[test]
+    fn uses_steam_path_as_is() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = steam_root(&dir.path().join("Steam"));
+
+        let steam = Steam::locate(Some(&root)).unwrap();
+        assert_eq!(steam.root(), root);
+        assert_eq!(steam.kind(), InstallKind::Native);
+
+        let flatpak = steam_root(
+            &dir.path()
+                .join(".var/app/com.valvesoftware.Steam/.local/share/Steam"),
+        );
+        assert_eq!(
+            Steam::locate(Some(&flatpak)).unwrap().kind(),
+            InstallKind::Flatpak
+        );
+        let snap = steam_root(&dir.path().join("snap/steam/common/.local/share/Steam"));
+        assert_eq!(
+            Steam::locate(Some(&snap)).unwrap().kind(),
+            InstallKind::Snap
+        );
+
+        assert!(matches!(
+            Steam::locate(Some(&dir.path().join("missing"))),
+            Err(SteamError::InvalidPath(_))
+        ));
+    }
+
+    #[test]
+    fn finds_the_first_install() {
+        let dir = tempfile::tempdir().unwrap();
+        let native = dir.path().join(".local/share/Steam");
+        let flatpak = steam_root(&dir.path().join("flatpak"));
+        let candidates = || {
+            vec![
+                (native.clone(), InstallKind::Native),
+                (flatpak.clone(), InstallKind::Flatpak),
+            ]
+        };
+
+        let steam = Steam::find(candidates()).unwrap();
+        assert_eq!(steam.kind(), InstallKind::Flatpak);
+        assert_eq!(steam.root(), std::fs::canonicalize(&flatpak).unwrap());
+
+        // Folders without `config` or `steamapps` are leftovers, not installs
+        std::fs::create_dir_all(native.join("logs")).unwrap();
+        assert_eq!(
+            Steam::find(candidates()).unwrap().kind(),
+            InstallKind::Flatpak
+        );
+        std::fs::create_dir_all(native.join("steamapps")).unwrap();
+        assert_eq!(
+            Steam::find(candidates()).unwrap().kind(),
+            InstallKind::Native
+        );
+
+        assert!(Steam::find(vec![(dir.path().join("none"), InstallKind::Native)]).is_none());
+    }
+
+    #[test]
+    fn reads_users() {
+        let dir = tempfile::tempdir().unwrap();
+        let steam = Steam::locate(Some(&steam_root(dir.path()))).unwrap();
+        assert!(matches!(steam.users(), Err(SteamError::ReadUsers(_))));
+
+        std::fs::write(
+            dir.path().join("config/loginusers.vdf"),
+            r#"
+            "users"
+            {
+                "76561197960265830"
+                {
+                    "AccountName"   "old"
+                    "PersonaName"   "Old"
+                    "MostRecent"    "0"
+                    "Timestamp"     "100"
+                }
+                "76561197960265831"
+                {
+                    "AccountName"   "newer"
+                    "Timestamp"     "200"
+                }
+                "76561197960265729"
+                {
+                    "AccountName"   "recent"
+                    "PersonaName"   "Recent"
+                    "MostRecent"    "1"
+                    "Timestamp"     "50"
+                }
+                "not-an-id"
+                {
+                    "AccountName"   "skipped"
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let users = steam.users().unwrap();
+        let names: Vec<_> = users.iter().map(|u| u.account_name.as_str()).collect();
+        assert_eq!(names, ["recent", "newer", "old"]);
+        assert_eq!(users[0].account_id, 1);
+        assert!(users[0].most_recent);
+        assert_eq!(users[1].persona_name, "");
+        assert!(!users[1].most_recent);
+
+        assert_eq!(steam.user(None).unwrap().account_name, "recent");
+        assert_eq!(steam.user(Some(102)).unwrap().account_name, "old");
+        assert!(matches!(
+            steam.user(Some(7)),
+            Err(SteamError::UnknownUser(7))
+        ));
+
+        std::fs::write(dir.path().join("config/loginusers.vdf"), "\"users\" {}").unwrap();
+        assert!(matches!(steam.user(None), Err(SteamError::NoUsers)));
+        std::fs::write(dir.path().join("config/loginusers.vdf"), "\"users\" {").unwrap();
+        assert!(matches!(steam.users(), Err(SteamError::ParseUsers(_))));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn finds_steam_processes() {
+        let dir = tempfile::tempdir().unwrap();
+        let process = |pid: &str, comm: &str| {
+            std::fs::create_dir_all(dir.path().join(pid)).unwrap();
+            std::fs::write(dir.path().join(pid).join("comm"), comm).unwrap();
+        };
+
+        process("1", "systemd\n");
+        process("self", "steam\n");
+        process("200", "steamwebhelper\n");
+        assert!(!has_steam_process(dir.path()));
+
+        process("300", "steam\n");
+        assert!(has_steam_process(dir.path()));
+        assert!(!has_steam_process(&dir.path().join("missing")));
+    }
+}
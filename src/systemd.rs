@@ -0,0 +1,107 @@
+/*
+ *  systemd.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! systemd user service support: the unit written by `conductor install-service` and
+//! `sd_notify` messages for `Type=notify` services.
+
+use std::path::{Path, PathBuf};
+
+/// Name of the unit written by `install-service`
+pub const UNIT_NAME: &str = "conductor.service";
+
+/// Sends a state change like `READY=1` or `STATUS=...` to systemd. Does nothing when Conductor
+/// wasn't started by systemd.
+pub fn notify(state: &str) {
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "linux")] {
+            use std::os::linux::net::SocketAddrExt;
+            use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+            let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+                return;
+            };
+            let path = path.to_string_lossy();
+
+            // Names starting with `@` are in the abstract namespace
+            let addr = match path.strip_prefix('@') {
+                Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+                None => SocketAddr::from_pathname(path.as_ref()),
+            };
+
+            let sent = addr.and_then(|addr| {
+                let socket = UnixDatagram::unbound()?;
+                socket.send_to_addr(state.as_bytes(), &addr)
+            });
+            if let Err(e) = sent {
+                log::debug!("Failed to notify systemd: {e}");
+            }
+        } else {
+            _ = state;
+        }
+    }
+}
+
+/// Quotes a path for `ExecStart`, escaping the characters systemd treats specially.
+fn quote(path: &Path) -> String {
+    let escaped = path
+        .to_string_lossy()
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%");
+    format!("\"{escaped}\"")
+}
+
+/// Returns a user unit that runs `exe` with the settings at `settings_path`.
+pub fn unit_file(exe: &Path, settings_path: &Path) -> String {
+    format!(
+        "[Unit]\n\
+         Description=Conductor, remote control for the Steam client\n\
+         After=graphical-session.target\n\
+         \n\
+         [Service]\n\
+         Type=notify\n\
+         ExecStart={} --settings-path {} start\n\
+         ExecReload=kill -HUP $MAINPID\n\
+         Restart=on-failure\n\
+         RestartSec=5\n\
+         # Steam can take a while to open its debugger after a restart\n\
+         TimeoutStartSec=180\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        quote(exe),
+        quote(settings_path)
+    )
+}
+
+/// Folder for user units, usually `~/.config/systemd/user`
+pub fn user_unit_dir() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("systemd").join("user"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_quotes_paths() {
+        let unit = unit_file(
+            Path::new("/opt/Conductor 1/conductor"),
+            Path::new("/home/deck/100%/settings.toml"),
+        );
+
+        assert!(unit.contains(
+            "ExecStart=\"/opt/Conductor 1/conductor\" --settings-path \"/home/deck/100%%/settings.toml\" start\n"
+        ));
+        assert!(unit.contains("Type=notify\n"));
+    }
+}
@@ -0,0 +1,535 @@
+/*
+ *  tokens.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+//! Client tokens and how they're checked. Tokens made with `conductor token add` are stored as
+//! salted SHA-256 hashes, plain `auth.tokens` from older settings files still work. Every
+//! comparison takes the same time no matter where the secrets differ.
+//!
+//! Shared keys for signed requests (see [`crate::signing`]) live next to the tokens. The server
+//! needs them to check signatures, so they're stored as they are.
+//!
+//! Session tokens from `RefreshToken` only live in memory and last until they expire, the server
+//! restarts or the token they were made from is revoked.
+
+use crate::config::AuthConfig;
+use crate::secrets::generate_secret;
+use chrono::{DateTime, SubsecRound, TimeDelta, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+
+/// Prefix of the `hash` field, so the scheme can change later
+const HASH_SCHEME: &str = "sha256";
+
+/// How long session tokens last if the client doesn't ask for a time, in seconds
+const DEFAULT_SESSION_TTL: u32 = 60 * 60;
+
+/// Longest a session token can last, in seconds
+const MAX_SESSION_TTL: u32 = 24 * 60 * 60;
+
+/// A token stored in `[[auth.hashed_tokens]]`
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct HashedToken {
+    pub name: String,
+    /// `sha256:<salt>:<hash>`, both in hex
+    pub hash: String,
+    pub created_at: DateTime<Utc>,
+    /// The token is rejected before this time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Commands this token can run, all of them if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+}
+
+/// A key for signed requests stored in `[[auth.signing_keys]]`
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct SharedKey {
+    /// Sent as `keyId` in signatures
+    pub name: String,
+    pub key: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Commands this key can sign, all of them if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+}
+
+impl SharedKey {
+    /// Makes a new random key.
+    pub fn generate(
+        name: String,
+        not_before: Option<DateTime<Utc>>,
+        expires_at: Option<DateTime<Utc>>,
+        scopes: Option<Vec<String>>,
+    ) -> Self {
+        Self {
+            name,
+            key: generate_secret(),
+            created_at: Utc::now().trunc_subsecs(0),
+            not_before,
+            expires_at,
+            scopes,
+        }
+    }
+
+    /// Checks that the key can sign `command` at `now`, and returns the credential for it.
+    pub fn authorize(&self, command: &str, now: DateTime<Utc>) -> Result<Credential, AuthError> {
+        check_time(self.not_before, self.expires_at, now)?;
+        if !allows(self.scopes.as_deref(), command) {
+            return Err(AuthError::OutOfScope(command.to_owned()));
+        }
+        Ok(Credential::Token {
+            parent: ParentToken::Shared(self.key.clone()),
+            expires_at: self.expires_at,
+            scopes: self.scopes.clone(),
+        })
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum AuthError {
+    #[error("A secret is required")]
+    MissingSecret,
+    #[error("Wrong secret! Are you a hacker?")]
+    WrongSecret,
+    #[error("This token has expired")]
+    Expired,
+    #[error("This token is not valid yet")]
+    NotYetValid,
+    #[error("The token this session was made from has been revoked")]
+    Revoked,
+    #[error("Invalid signature")]
+    BadSignature,
+    #[error("Request timestamp is too far from the server's clock")]
+    StaleTimestamp,
+    #[error("This request was already received")]
+    Replayed,
+    #[error("This token can't run {0}")]
+    OutOfScope(String),
+    #[error("Too many failed attempts, try again in {0} seconds")]
+    LockedOut(i64),
+}
+
+fn hash(salt: &[u8], token: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(token.as_bytes());
+    hasher.finalize().into()
+}
+
+impl HashedToken {
+    /// Makes a new random token. Returns the entry to store and the plain token, which isn't
+    /// kept anywhere.
+    pub fn generate(
+        name: String,
+        not_before: Option<DateTime<Utc>>,
+        expires_at: Option<DateTime<Utc>>,
+        scopes: Option<Vec<String>>,
+    ) -> (Self, String) {
+        let token = generate_secret();
+        let mut salt = [0; 16];
+        rand::rng().fill_bytes(&mut salt);
+
+        let entry = Self {
+            name,
+            hash: format!(
+                "{HASH_SCHEME}:{}:{}",
+                hex::encode(salt),
+                hex::encode(hash(&salt, &token))
+            ),
+            created_at: Utc::now().trunc_subsecs(0),
+            not_before,
+            expires_at,
+            scopes,
+        };
+        (entry, token)
+    }
+
+    /// Returns `true` if `token` hashes to the stored value. Entries with a malformed hash never
+    /// match.
+    pub fn matches(&self, token: &str) -> bool {
+        let mut parts = self.hash.split(':');
+        let (Some(HASH_SCHEME), Some(salt), Some(expected), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return false;
+        };
+        let (Ok(salt), Ok(expected)) = (hex::decode(salt), hex::decode(expected)) else {
+            return false;
+        };
+
+        hash(&salt, token).ct_eq(&expected).into()
+    }
+
+    /// Returns `true` if the stored hash can be checked at all
+    pub fn is_valid(&self) -> bool {
+        let mut parts = self.hash.split(':');
+        parts.next() == Some(HASH_SCHEME)
+            && parts.next().is_some_and(|salt| hex::decode(salt).is_ok())
+            && parts
+                .next()
+                .is_some_and(|hash| hex::decode(hash).is_ok_and(|hash| hash.len() == 32))
+            && parts.next().is_none()
+    }
+}
+
+/// Returns an error if a token with these limits can't be used at `now`
+fn check_time(
+    not_before: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<(), AuthError> {
+    if not_before.is_some_and(|not_before| now < not_before) {
+        return Err(AuthError::NotYetValid);
+    }
+    if expires_at.is_some_and(|expires_at| expires_at <= now) {
+        return Err(AuthError::Expired);
+    }
+    Ok(())
+}
+
+fn allows(scopes: Option<&[String]>, command: &str) -> bool {
+    scopes.is_none_or(|scopes| scopes.iter().any(|scope| scope == command))
+}
+
+/// Long-lived token from the settings file
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParentToken {
+    /// From `auth.tokens`
+    Plain(String),
+    /// From `auth.hashed_tokens`, by its hash
+    Hashed(String),
+    /// From `auth.signing_keys`, by the key itself
+    Shared(String),
+}
+
+impl ParentToken {
+    /// Returns `true` if the token is still in the settings and can be used at `now`
+    fn is_current(&self, auth: &AuthConfig, now: DateTime<Utc>) -> bool {
+        match self {
+            ParentToken::Plain(token) => auth.tokens.iter().flatten().any(|t| t == token),
+            ParentToken::Hashed(hash) => auth
+                .hashed_tokens
+                .iter()
+                .any(|t| t.hash == *hash && check_time(t.not_before, t.expires_at, now).is_ok()),
+            ParentToken::Shared(key) => auth
+                .signing_keys
+                .iter()
+                .any(|k| k.key == *key && check_time(k.not_before, k.expires_at, now).is_ok()),
+        }
+    }
+}
+
+/// What a request was authorized with
+#[derive(Clone, Debug, PartialEq)]
+pub enum Credential {
+    /// Auth is off
+    Anonymous,
+    /// A token from the settings file
+    Token {
+        parent: ParentToken,
+        expires_at: Option<DateTime<Utc>>,
+        scopes: Option<Vec<String>>,
+    },
+    /// A session token from `RefreshToken`
+    Session,
+}
+
+struct Session {
+    parent: ParentToken,
+    expires_at: DateTime<Utc>,
+    scopes: Option<Vec<String>>,
+}
+
+/// Session tokens handed out by `RefreshToken`, stored by their SHA-256 hash so looking one up
+/// doesn't leak anything about the token.
+#[derive(Default)]
+pub struct Sessions(Mutex<HashMap<[u8; 32], Session>>);
+
+impl Sessions {
+    /// Makes a session token that lasts `ttl` seconds, but not longer than the token it's made
+    /// from. Returns the token and when it expires.
+    pub fn create(
+        &self,
+        credential: &Credential,
+        ttl: Option<u32>,
+        now: DateTime<Utc>,
+    ) -> Result<(String, DateTime<Utc>), String> {
+        let (parent, parent_expires_at, scopes) = match credential {
+            Credential::Token {
+                parent,
+                expires_at,
+                scopes,
+            } => (parent, expires_at, scopes),
+            Credential::Anonymous => return Err("Authentication is off".to_owned()),
+            Credential::Session => {
+                return Err("Session tokens can't be refreshed, use the original token".to_owned());
+            }
+        };
+
+        let ttl = ttl.unwrap_or(DEFAULT_SESSION_TTL);
+        if !(1..=MAX_SESSION_TTL).contains(&ttl) {
+            return Err(format!("ttl must be from 1 to {MAX_SESSION_TTL} seconds"));
+        }
+        let mut expires_at = now + TimeDelta::seconds(ttl.into());
+        if let Some(parent_expires_at) = *parent_expires_at {
+            expires_at = expires_at.min(parent_expires_at);
+        }
+
+        let token = generate_secret();
+        let mut sessions = self.0.lock().unwrap();
+        sessions.retain(|_, session| session.expires_at > now);
+        sessions.insert(
+            hash(&[], &token),
+            Session {
+                parent: parent.clone(),
+                expires_at,
+                scopes: scopes.clone(),
+            },
+        );
+
+        Ok((token, expires_at))
+    }
+
+    fn check(
+        &self,
+        auth: &AuthConfig,
+        secret: &str,
+        command: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Credential, AuthError> {
+        let mut sessions = self.0.lock().unwrap();
+        let key = hash(&[], secret);
+        let session = sessions.get(&key).ok_or(AuthError::WrongSecret)?;
+
+        if session.expires_at <= now {
+            sessions.remove(&key);
+            return Err(AuthError::Expired);
+        }
+        if !session.parent.is_current(auth, now) {
+            sessions.remove(&key);
+            return Err(AuthError::Revoked);
+        }
+        if !allows(session.scopes.as_deref(), command) {
+            return Err(AuthError::OutOfScope(command.to_owned()));
+        }
+        Ok(Credential::Session)
+    }
+}
+
+/// Parses a time for `conductor token add`, either a date like `2025-07-01T00:00:00Z` or a
+/// time from `now` like `30m`, `12h`, `7d` or `2w`.
+pub fn parse_time(value: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date.to_utc());
+    }
+
+    let invalid = || format!("{value:?} is not a date or a time like 12h or 7d");
+    let unit = value.chars().last().ok_or_else(invalid)?;
+    let amount: i64 = value[..value.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| invalid())?;
+
+    let delta = match unit {
+        'm' => TimeDelta::try_minutes(amount),
+        'h' => TimeDelta::try_hours(amount),
+        'd' => TimeDelta::try_days(amount),
+        'w' => TimeDelta::try_weeks(amount),
+        _ => None,
+    };
+    delta
+        .filter(|delta| *delta > TimeDelta::zero())
+        .and_then(|delta| now.checked_add_signed(delta))
+        .ok_or_else(invalid)
+}
+
+/// Checks that `secret` belongs to a token or session that can run `command` right now, and
+/// returns which one it was. Everything is allowed when auth is off.
+pub fn authorize(
+    auth: Option<&AuthConfig>,
+    sessions: &Sessions,
+    secret: Option<&str>,
+    command: &str,
+    now: DateTime<Utc>,
+) -> Result<Credential, AuthError> {
+    let Some(auth) = auth.filter(|auth| auth.enabled) else {
+        return Ok(Credential::Anonymous);
+    };
+    let secret = secret.ok_or(AuthError::MissingSecret)?;
+
+    // Check every token so the time taken doesn't depend on which one matched
+    let plain = auth.tokens.iter().flatten().fold(None, |found, token| {
+        let matches = bool::from(token.as_bytes().ct_eq(secret.as_bytes()));
+        found.or(matches.then_some(token))
+    });
+    if let Some(token) = plain {
+        return Ok(Credential::Token {
+            parent: ParentToken::Plain(token.clone()),
+            expires_at: None,
+            scopes: None,
+        });
+    }
+
+    let hashed = auth.hashed_tokens.iter().fold(None, |found, token| {
+        found.or(token.matches(secret).then_some(token))
+    });
+    let Some(token) = hashed else {
+        return sessions.check(auth, secret, command, now);
+    };
+
+    check_time(token.not_before, token.expires_at, now)?;
+    if !allows(token.scopes.as_deref(), command) {
+        return Err(AuthError::OutOfScope(command.to_owned()));
+    }
+    Ok(Credential::Token {
+        parent: ParentToken::Hashed(token.hash.clone()),
+        expires_at: token.expires_at,
+        scopes: token.scopes.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth(hashed_tokens: Vec<HashedToken>) -> AuthConfig {
+        AuthConfig {
+            enabled: true,
+            tokens: Some(vec!["plain".to_owned()]),
+            hashed_tokens,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn hashes_are_salted() {
+        let (first, token) = HashedToken::generate("first".to_owned(), None, None, None);
+        let (second, _) = HashedToken::generate("second".to_owned(), None, None, None);
+
+        assert!(first.is_valid());
+        assert!(!first.hash.contains(&token));
+        assert!(first.matches(&token));
+        assert!(!second.matches(&token));
+        assert_ne!(first.hash, second.hash);
+    }
+
+    #[test]
+    fn checks_tokens() {
+        let now = Utc::now();
+        let hour = TimeDelta::hours(1);
+        let (scoped, scoped_token) = HashedToken::generate(
+            "scoped".to_owned(),
+            None,
+            None,
+            Some(vec!["GetApps".to_owned()]),
+        );
+        let (expired, expired_token) =
+            HashedToken::generate("expired".to_owned(), None, Some(now - hour), None);
+        let (pending, pending_token) =
+            HashedToken::generate("pending".to_owned(), Some(now + hour), None, None);
+        let auth = auth(vec![scoped, expired, pending]);
+        let sessions = Sessions::default();
+
+        let check =
+            |secret, command| authorize(Some(&auth), &sessions, secret, command, now).map(|_| ());
+        assert_eq!(check(Some("plain"), "RunApp"), Ok(()));
+        assert_eq!(check(Some(&scoped_token), "GetApps"), Ok(()));
+        assert_eq!(
+            check(Some(&scoped_token), "RunApp"),
+            Err(AuthError::OutOfScope("RunApp".to_owned()))
+        );
+        assert_eq!(
+            check(Some(&expired_token), "GetApps"),
+            Err(AuthError::Expired)
+        );
+        assert_eq!(
+            check(Some(&pending_token), "GetApps"),
+            Err(AuthError::NotYetValid)
+        );
+        assert_eq!(check(Some("nope"), "GetApps"), Err(AuthError::WrongSecret));
+        assert_eq!(check(None, "GetApps"), Err(AuthError::MissingSecret));
+        assert_eq!(
+            authorize(None, &sessions, None, "GetApps", now),
+            Ok(Credential::Anonymous)
+        );
+    }
+
+    #[test]
+    fn sessions_follow_their_token() {
+        let now = Utc::now();
+        let (token, secret) = HashedToken::generate(
+            "laptop".to_owned(),
+            None,
+            Some(now + TimeDelta::minutes(10)),
+            Some(vec!["GetApps".to_owned(), "RefreshToken".to_owned()]),
+        );
+        let mut auth = auth(vec![token]);
+        let sessions = Sessions::default();
+
+        let credential =
+            authorize(Some(&auth), &sessions, Some(&secret), "RefreshToken", now).unwrap();
+        let (session, expires_at) = sessions.create(&credential, None, now).unwrap();
+        // Capped to the expiry of the original token
+        assert_eq!(expires_at, now + TimeDelta::minutes(10));
+        assert!(sessions.create(&credential, Some(0), now).is_err());
+
+        let check = |auth: &AuthConfig, command, now| {
+            authorize(Some(auth), &sessions, Some(&session), command, now)
+        };
+        assert_eq!(check(&auth, "GetApps", now), Ok(Credential::Session));
+        assert_eq!(
+            check(&auth, "RunApp", now),
+            Err(AuthError::OutOfScope("RunApp".to_owned()))
+        );
+        assert!(sessions.create(&Credential::Session, None, now).is_err());
+        assert_eq!(check(&auth, "GetApps", expires_at), Err(AuthError::Expired));
+
+        let (session, _) = sessions.create(&credential, Some(60), now).unwrap();
+        auth.hashed_tokens.clear();
+        assert_eq!(
+            authorize(Some(&auth), &sessions, Some(&session), "GetApps", now),
+            Err(AuthError::Revoked)
+        );
+    }
+
+    #[test]
+    fn parses_times() {
+        let now = Utc::now();
+        assert_eq!(parse_time("12h", now), Ok(now + TimeDelta::hours(12)));
+        assert_eq!(parse_time("2w", now), Ok(now + TimeDelta::weeks(2)));
+        assert_eq!(
+            parse_time("2025-07-01T02:00:00+02:00", now)
+                .unwrap()
+                .to_rfc3339(),
+            "2025-07-01T00:00:00+00:00"
+        );
+        assert!(parse_time("0d", now).is_err());
+        assert!(parse_time("7y", now).is_err());
+        assert!(parse_time("soon", now).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_hashes() {
+        let (mut token, secret) = HashedToken::generate("broken".to_owned(), None, None, None);
+        token.hash = token.hash.replacen(HASH_SCHEME, "md5", 1);
+
+        assert!(!token.is_valid());
+        assert!(!token.matches(&secret));
+    }
+}
@@ -0,0 +1,471 @@
+/*
+ *  vdf.rs
+ *  (c) 2025 Teodor Potancok
+ *
+ *  This Source Code Form is subject to the terms of the Mozilla Public
+ *  License, v. 2.0. If a copy of the MPL was not distributed with this
+ *  file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+use thiserror::Error;
+
+const TYPE_MAP: u8 = 0x00;
+const TYPE_STRING: u8 = 0x01;
+const TYPE_INT32: u8 = 0x02;
+const TYPE_FLOAT32: u8 = 0x03;
+const TYPE_UINT64: u8 = 0x07;
+const TYPE_MAP_END: u8 = 0x08;
+const TYPE_INT64: u8 = 0x0A;
+
+/// Deepest nesting allowed when reading, Steam's files go a few levels deep at most. Keeps a
+/// crafted file from overflowing the stack.
+const MAX_DEPTH: usize = 64;
+
+/// Value stored in a VDF key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Map(Map),
+    String(String),
+    Int32(i32),
+    Float32(f32),
+    UInt64(u64),
+    Int64(i64),
+}
+
+/// Ordered list of VDF keys. Steam looks up keys without caring about case so the getters here
+/// do the same, but the original spelling and order are kept so files can be written back unchanged.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Map(Vec<(String, Value)>);
+
+impl Map {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.0
+            .iter_mut()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v)
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        match self.get(key) {
+            Some(Value::String(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn get_i32(&self, key: &str) -> Option<i32> {
+        match self.get(key) {
+            Some(Value::Int32(i)) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn get_map(&self, key: &str) -> Option<&Map> {
+        match self.get(key) {
+            Some(Value::Map(m)) => Some(m),
+            _ => None,
+        }
+    }
+
+    /// Sets `key` to `value`, replacing an existing key with any casing in place.
+    pub fn insert(&mut self, key: impl Into<String>, value: Value) {
+        let key = key.into();
+        match self.get_mut(&key) {
+            Some(v) => *v = value,
+            None => self.0.push((key, value)),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v))
+    }
+}
+
+impl FromIterator<(String, Value)> for Map {
+    fn from_iter<T: IntoIterator<Item = (String, Value)>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl IntoIterator for Map {
+    type Item = (String, Value);
+    type IntoIter = std::vec::IntoIter<(String, Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum VdfError {
+    #[error("Unexpected end of data at byte {0}")]
+    UnexpectedEof(usize),
+    #[error("Unknown value type {0:#04x} at byte {1}")]
+    UnknownType(u8, usize),
+    #[error("Invalid UTF-8 string at byte {0}")]
+    InvalidString(usize),
+    #[error("Unexpected data after the end of the root map at byte {0}")]
+    TrailingData(usize),
+    #[error("Unexpected {0} on line {1}")]
+    UnexpectedToken(&'static str, usize),
+    #[error("Maps are nested more than {MAX_DEPTH} levels deep")]
+    TooDeep,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, VdfError> {
+        let b = *self
+            .bytes
+            .get(self.pos)
+            .ok_or(VdfError::UnexpectedEof(self.pos))?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], VdfError> {
+        let Some(slice) = self.bytes.get(self.pos..self.pos + N) else {
+            return Err(VdfError::UnexpectedEof(self.bytes.len()));
+        };
+        self.pos += N;
+        Ok(slice.try_into().expect("slice has the right length"))
+    }
+
+    fn string(&mut self) -> Result<String, VdfError> {
+        let start = self.pos;
+        let Some(len) = self.bytes[start..].iter().position(|b| *b == 0) else {
+            return Err(VdfError::UnexpectedEof(self.bytes.len()));
+        };
+        self.pos += len + 1;
+        String::from_utf8(self.bytes[start..start + len].to_vec())
+            .map_err(|_| VdfError::InvalidString(start))
+    }
+
+    fn map(&mut self, depth: usize) -> Result<Map, VdfError> {
+        if depth > MAX_DEPTH {
+            return Err(VdfError::TooDeep);
+        }
+        let mut map = Map::new();
+
+        loop {
+            let type_pos = self.pos;
+            let value_type = self.byte()?;
+            if value_type == TYPE_MAP_END {
+                return Ok(map);
+            }
+
+            let key = self.string()?;
+            let value = match value_type {
+                TYPE_MAP => Value::Map(self.map(depth + 1)?),
+                TYPE_STRING => Value::String(self.string()?),
+                TYPE_INT32 => Value::Int32(i32::from_le_bytes(self.array()?)),
+                TYPE_FLOAT32 => Value::Float32(f32::from_le_bytes(self.array()?)),
+                TYPE_UINT64 => Value::UInt64(u64::from_le_bytes(self.array()?)),
+                TYPE_INT64 => Value::Int64(i64::from_le_bytes(self.array()?)),
+                other => return Err(VdfError::UnknownType(other, type_pos)),
+            };
+
+            map.0.push((key, value));
+        }
+    }
+}
+
+/// Parses a binary VDF file like `shortcuts.vdf` into its root map.
+pub fn read_binary(bytes: &[u8]) -> Result<Map, VdfError> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let map = reader.map(0)?;
+
+    if reader.pos != bytes.len() {
+        return Err(VdfError::TrailingData(reader.pos));
+    }
+
+    Ok(map)
+}
+
+fn write_map(map: &Map, out: &mut Vec<u8>) {
+    for (key, value) in map.iter() {
+        let value_type = match value {
+            Value::Map(_) => TYPE_MAP,
+            Value::String(_) => TYPE_STRING,
+            Value::Int32(_) => TYPE_INT32,
+            Value::Float32(_) => TYPE_FLOAT32,
+            Value::UInt64(_) => TYPE_UINT64,
+            Value::Int64(_) => TYPE_INT64,
+        };
+
+        out.push(value_type);
+        out.extend_from_slice(key.as_bytes());
+        out.push(0);
+
+        match value {
+            Value::Map(m) => write_map(m, out),
+            Value::String(s) => {
+                out.extend_from_slice(s.as_bytes());
+                out.push(0);
+            }
+            Value::Int32(i) => out.extend_from_slice(&i.to_le_bytes()),
+            Value::Float32(f) => out.extend_from_slice(&f.to_le_bytes()),
+            Value::UInt64(u) => out.extend_from_slice(&u.to_le_bytes()),
+            Value::Int64(i) => out.extend_from_slice(&i.to_le_bytes()),
+        }
+    }
+
+    out.push(TYPE_MAP_END);
+}
+
+/// Serializes a root map in the binary VDF format. Output of `read_binary` is written back byte for byte.
+pub fn write_binary(map: &Map) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_map(map, &mut out);
+    out
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    String(String),
+    Open,
+    Close,
+}
+
+struct Tokenizer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl Tokenizer<'_> {
+    fn next_token(&mut self) -> Result<Option<Token>, VdfError> {
+        loop {
+            let Some(c) = self.chars.next() else {
+                return Ok(None);
+            };
+
+            match c {
+                '\n' => self.line += 1,
+                c if c.is_whitespace() => {}
+                '/' if self.chars.peek() == Some(&'/') => {
+                    for c in self.chars.by_ref() {
+                        if c == '\n' {
+                            self.line += 1;
+                            break;
+                        }
+                    }
+                }
+                // Platform conditionals like `[$WIN32]`, Conductor reads every key
+                '[' => {
+                    for c in self.chars.by_ref() {
+                        if c == ']' {
+                            break;
+                        }
+                    }
+                }
+                '{' => return Ok(Some(Token::Open)),
+                '}' => return Ok(Some(Token::Close)),
+                '"' => {
+                    let mut s = String::new();
+                    loop {
+                        match self.chars.next() {
+                            None => {
+                                return Err(VdfError::UnexpectedToken("end of file", self.line));
+                            }
+                            Some('"') => break,
+                            Some('\\') => match self.chars.next() {
+                                Some('n') => s.push('\n'),
+                                Some('t') => s.push('\t'),
+                                Some(c) => s.push(c),
+                                None => {
+                                    return Err(VdfError::UnexpectedToken(
+                                        "end of file",
+                                        self.line,
+                                    ));
+                                }
+                            },
+                            Some(c) => {
+                                if c == '\n' {
+                                    self.line += 1;
+                                }
+                                s.push(c);
+                            }
+                        }
+                    }
+                    return Ok(Some(Token::String(s)));
+                }
+                c => {
+                    let mut s = String::from(c);
+                    while let Some(c) = self.chars.peek() {
+                        if c.is_whitespace() || matches!(c, '{' | '}' | '"') {
+                            break;
+                        }
+                        s.push(*c);
+                        self.chars.next();
+                    }
+                    return Ok(Some(Token::String(s)));
+                }
+            }
+        }
+    }
+
+    fn map(&mut self, depth: usize) -> Result<Map, VdfError> {
+        if depth > MAX_DEPTH {
+            return Err(VdfError::TooDeep);
+        }
+        let nested = depth > 0;
+        let mut map = Map::new();
+
+        loop {
+            let key = match self.next_token()? {
+                Some(Token::String(key)) => key,
+                Some(Token::Close) if nested => return Ok(map),
+                None if !nested => return Ok(map),
+                Some(Token::Close) => return Err(VdfError::UnexpectedToken("`}`", self.line)),
+                Some(Token::Open) => return Err(VdfError::UnexpectedToken("`{`", self.line)),
+                None => return Err(VdfError::UnexpectedToken("end of file", self.line)),
+            };
+
+            let value = match self.next_token()? {
+                Some(Token::String(value)) => Value::String(value),
+                Some(Token::Open) => Value::Map(self.map(depth + 1)?),
+                Some(Token::Close) => return Err(VdfError::UnexpectedToken("`}`", self.line)),
+                None => return Err(VdfError::UnexpectedToken("end of file", self.line)),
+            };
+
+            map.0.push((key, value));
+        }
+    }
+}
+
+/// Parses a text VDF file like `loginusers.vdf` or `libraryfolders.vdf`. All values are read as strings.
+pub fn read_text(text: &str) -> Result<Map, VdfError> {
+    Tokenizer {
+        chars: text.chars().peekable(),
+        line: 1,
+    }
+    .map(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_all_types() {
+        let inner: Map = [
+            ("name".to_owned(), Value::String("Half-Life".to_owned())),
+            ("int".to_owned(), Value::Int32(-42)),
+            ("float".to_owned(), Value::Float32(1.5)),
+            ("big".to_owned(), Value::UInt64(76561197960265728)),
+            ("signed".to_owned(), Value::Int64(-1)),
+            ("empty".to_owned(), Value::Map(Map::new())),
+        ]
+        .into_iter()
+        .collect();
+        let root: Map = [("root".to_owned(), Value::Map(inner))]
+            .into_iter()
+            .collect();
+
+        let bytes = write_binary(&root);
+        let parsed = read_binary(&bytes).unwrap();
+
+        assert_eq!(parsed, root);
+        assert_eq!(write_binary(&parsed), bytes);
+    }
+
+    #[test]
+    fn case_insensitive_lookup() {
+        let mut map = Map::new();
+        map.insert("AppName", Value::String("a".to_owned()));
+        map.insert("appname", Value::String("b".to_owned()));
+
+        assert_eq!(map.iter().count(), 1);
+        assert_eq!(map.get_str("APPNAME"), Some("b"));
+        assert!(map.iter().all(|(k, _)| k == "AppName"));
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let bytes = b"\x00shortcuts\x00\x01AppName\x00Game";
+        assert!(matches!(
+            read_binary(bytes),
+            Err(VdfError::UnexpectedEof(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_type() {
+        let bytes = b"\x09key\x00\x08";
+        assert!(matches!(
+            read_binary(bytes),
+            Err(VdfError::UnknownType(0x09, 0))
+        ));
+    }
+
+    #[test]
+    fn parse_text() {
+        let text = r#"
+            // comment
+            "users"
+            {
+                "76561197960287930"
+                {
+                    "AccountName"   "gaben"
+                    "PersonaName"   "Rabscuttle \"Gabe\""
+                    "MostRecent"    "1"
+                }
+                unquoted value [$WIN32]
+            }
+        "#;
+
+        let root = read_text(text).unwrap();
+        let users = root.get_map("users").unwrap();
+        let user = users.get_map("76561197960287930").unwrap();
+
+        assert_eq!(user.get_str("accountname"), Some("gaben"));
+        assert_eq!(user.get_str("PersonaName"), Some("Rabscuttle \"Gabe\""));
+        assert_eq!(users.get_str("unquoted"), Some("value"));
+    }
+
+    #[test]
+    fn text_errors_have_line_numbers() {
+        assert!(matches!(
+            read_text("\"a\"\n{\n\"b\"\n}"),
+            Err(VdfError::UnexpectedToken("`}`", 4))
+        ));
+        assert!(matches!(
+            read_text("\"a\" {"),
+            Err(VdfError::UnexpectedToken("end of file", 1))
+        ));
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        let mut bytes = b"\x00a\x00".repeat(100_000);
+        bytes.extend([TYPE_MAP_END; 100_001]);
+        assert!(matches!(read_binary(&bytes), Err(VdfError::TooDeep)));
+
+        let text = "a {".repeat(100_000);
+        assert!(matches!(read_text(&text), Err(VdfError::TooDeep)));
+
+        let mut bytes = b"\x00a\x00".repeat(MAX_DEPTH);
+        bytes.extend([TYPE_MAP_END; MAX_DEPTH + 1]);
+        assert!(read_binary(&bytes).is_ok());
+    }
+
+    #[test]
+    fn rejects_trailing_data() {
+        let bytes = b"\x08\x08";
+        assert!(matches!(read_binary(bytes), Err(VdfError::TrailingData(1))));
+    }
+}